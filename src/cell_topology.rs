@@ -0,0 +1,285 @@
+use bevy::prelude::*;
+
+pub const FACE_COUNT: usize = 12;
+pub const VERTEX_COUNT: usize = 14;
+pub const EDGE_COUNT: usize = 24;
+
+const H: f32 = 0.5;
+const S: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// The corners of a cell centered at the origin: the 6 4-valent ones, ordered like
+/// [`crate::SECOND_SHELL_OFFSETS`], then the 8 3-valent ones.
+pub const CELL_VERTICES: [Vec3; VERTEX_COUNT] = [
+    Vec3::new(1.0, 0.0, 0.0),
+    Vec3::new(0.0, 1.0, 0.0),
+    Vec3::new(0.0, 0.0, 1.0),
+    Vec3::new(-1.0, 0.0, 0.0),
+    Vec3::new(0.0, -1.0, 0.0),
+    Vec3::new(0.0, 0.0, -1.0),
+    Vec3::new(H, H, H),
+    Vec3::new(H, H, -H),
+    Vec3::new(H, -H, H),
+    Vec3::new(H, -H, -H),
+    Vec3::new(-H, H, H),
+    Vec3::new(-H, H, -H),
+    Vec3::new(-H, -H, H),
+    Vec3::new(-H, -H, -H),
+];
+
+/// Faces are indexed like [`crate::NEIGHBOR_OFFSETS`], so the cell across face `i` is
+/// `cell.neighbor(i)` and the opposite face is `(i + 6) % 12`.
+pub const FACE_NORMALS: [Vec3; FACE_COUNT] = [
+    Vec3::new(S, S, 0.0),
+    Vec3::new(S, -S, 0.0),
+    Vec3::new(S, 0.0, S),
+    Vec3::new(S, 0.0, -S),
+    Vec3::new(0.0, S, S),
+    Vec3::new(0.0, S, -S),
+    Vec3::new(-S, -S, 0.0),
+    Vec3::new(-S, S, 0.0),
+    Vec3::new(-S, 0.0, -S),
+    Vec3::new(-S, 0.0, S),
+    Vec3::new(0.0, -S, -S),
+    Vec3::new(0.0, -S, S),
+];
+
+/// The corners of each face, counter-clockwise seen from outside. Corners 0 and 2 are 4-valent
+/// vertices and corners 1 and 3 are 3-valent ones.
+pub const FACE_VERTICES: [[usize; 4]; FACE_COUNT] = [
+    [0, 7, 1, 6],
+    [0, 8, 4, 9],
+    [2, 8, 0, 6],
+    [5, 7, 0, 9],
+    [1, 10, 2, 6],
+    [1, 7, 5, 11],
+    [3, 13, 4, 12],
+    [3, 10, 1, 11],
+    [5, 13, 3, 11],
+    [2, 10, 3, 12],
+    [4, 13, 5, 9],
+    [4, 8, 2, 12],
+];
+
+/// The 4-valent then the 3-valent end of each edge.
+pub const EDGE_VERTICES: [[usize; 2]; EDGE_COUNT] = [
+    [0, 7],
+    [1, 7],
+    [1, 6],
+    [0, 6],
+    [0, 8],
+    [4, 8],
+    [4, 9],
+    [0, 9],
+    [2, 8],
+    [2, 6],
+    [5, 7],
+    [5, 9],
+    [1, 10],
+    [2, 10],
+    [5, 11],
+    [1, 11],
+    [3, 13],
+    [4, 13],
+    [4, 12],
+    [3, 12],
+    [3, 10],
+    [3, 11],
+    [5, 13],
+    [2, 12],
+];
+
+/// The two faces meeting at each edge, lowest first.
+pub const EDGE_FACES: [[usize; 2]; EDGE_COUNT] = [
+    [0, 3],
+    [0, 5],
+    [0, 4],
+    [0, 2],
+    [1, 2],
+    [1, 11],
+    [1, 10],
+    [1, 3],
+    [2, 11],
+    [2, 4],
+    [3, 5],
+    [3, 10],
+    [4, 7],
+    [4, 9],
+    [5, 8],
+    [5, 7],
+    [6, 8],
+    [6, 10],
+    [6, 11],
+    [6, 9],
+    [7, 9],
+    [7, 8],
+    [8, 10],
+    [9, 11],
+];
+
+/// The edges of each face, edge `i` going from corner `i` to corner `i + 1` of
+/// [`FACE_VERTICES`].
+pub const FACE_EDGES: [[usize; 4]; FACE_COUNT] = [
+    [0, 1, 2, 3],
+    [4, 5, 6, 7],
+    [8, 4, 3, 9],
+    [10, 0, 7, 11],
+    [12, 13, 9, 2],
+    [1, 10, 14, 15],
+    [16, 17, 18, 19],
+    [20, 12, 15, 21],
+    [22, 16, 21, 14],
+    [13, 20, 19, 23],
+    [17, 22, 11, 6],
+    [5, 8, 23, 18],
+];
+
+/// The face across each edge in [`FACE_EDGES`].
+pub const FACE_ADJACENT_FACES: [[usize; 4]; FACE_COUNT] = [
+    [3, 5, 4, 2],
+    [2, 11, 10, 3],
+    [11, 1, 0, 4],
+    [5, 0, 1, 10],
+    [7, 9, 2, 0],
+    [0, 3, 8, 7],
+    [8, 10, 11, 9],
+    [9, 4, 5, 8],
+    [10, 6, 7, 5],
+    [4, 7, 6, 11],
+    [6, 8, 3, 1],
+    [1, 2, 9, 6],
+];
+
+/// The faces around each vertex, in increasing order.
+pub const VERTEX_FACES: [&[usize]; VERTEX_COUNT] = [
+    &[0, 1, 2, 3],
+    &[0, 4, 5, 7],
+    &[2, 4, 9, 11],
+    &[6, 7, 8, 9],
+    &[1, 6, 10, 11],
+    &[3, 5, 8, 10],
+    &[0, 2, 4],
+    &[0, 3, 5],
+    &[1, 2, 11],
+    &[1, 3, 10],
+    &[4, 7, 9],
+    &[5, 7, 8],
+    &[6, 9, 11],
+    &[6, 8, 10],
+];
+
+/// The edges around each vertex, in increasing order.
+pub const VERTEX_EDGES: [&[usize]; VERTEX_COUNT] = [
+    &[0, 3, 4, 7],
+    &[1, 2, 12, 15],
+    &[8, 9, 13, 23],
+    &[16, 19, 20, 21],
+    &[5, 6, 17, 18],
+    &[10, 11, 14, 22],
+    &[2, 3, 9],
+    &[0, 1, 10],
+    &[4, 5, 8],
+    &[6, 7, 11],
+    &[12, 13, 20],
+    &[14, 15, 21],
+    &[18, 19, 23],
+    &[16, 17, 22],
+];
+
+/// The number of faces and edges meeting at a vertex, 4 or 3.
+pub fn vertex_degree(vertex: usize) -> usize {
+    VERTEX_FACES[vertex].len()
+}
+
+/// The edge joining two vertices, if they share one.
+pub fn edge_between_vertices(a: usize, b: usize) -> Option<usize> {
+    EDGE_VERTICES
+        .iter()
+        .position(|&[c, d]| (c, d) == (a, b) || (c, d) == (b, a))
+}
+
+/// The edge two faces share, if they are adjacent.
+pub fn edge_between_faces(a: usize, b: usize) -> Option<usize> {
+    EDGE_FACES
+        .iter()
+        .position(|&[c, d]| (c, d) == (a, b) || (c, d) == (b, a))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NEIGHBOR_OFFSETS;
+
+    #[test]
+    fn vertex_tables_match_face_corners() {
+        for vertex in 0..VERTEX_COUNT {
+            let faces = (0..FACE_COUNT)
+                .filter(|&face| FACE_VERTICES[face].contains(&vertex))
+                .collect::<Vec<_>>();
+            assert_eq!(VERTEX_FACES[vertex], faces, "vertex {vertex}");
+            let edges = (0..EDGE_COUNT)
+                .filter(|&edge| EDGE_VERTICES[edge].contains(&vertex))
+                .collect::<Vec<_>>();
+            assert_eq!(VERTEX_EDGES[vertex], edges, "vertex {vertex}");
+            let expected_degree = if vertex < 6 { 4 } else { 3 };
+            assert_eq!(vertex_degree(vertex), expected_degree);
+        }
+    }
+
+    #[test]
+    fn edge_tables_match_face_corners() {
+        for face in 0..FACE_COUNT {
+            let corners = FACE_VERTICES[face];
+            for i in 0..4 {
+                let [a, b] = [corners[i], corners[(i + 1) % 4]];
+                let edge = FACE_EDGES[face][i];
+                // The 4-valent vertices come first, so they're the lesser end
+                assert_eq!(EDGE_VERTICES[edge], [a.min(b), a.max(b)]);
+                assert_eq!(edge_between_vertices(a, b), Some(edge));
+                assert!(EDGE_FACES[edge].contains(&face));
+            }
+        }
+        for (edge, &[a, b]) in EDGE_FACES.iter().enumerate() {
+            let faces = (0..FACE_COUNT)
+                .filter(|&face| FACE_EDGES[face].contains(&edge))
+                .collect::<Vec<_>>();
+            assert_eq!(faces, [a, b], "edge {edge}");
+            assert_eq!(edge_between_faces(a, b), Some(edge));
+        }
+    }
+
+    #[test]
+    fn adjacent_faces_match_shared_edges() {
+        for face in 0..FACE_COUNT {
+            for i in 0..4 {
+                let [a, b] = EDGE_FACES[FACE_EDGES[face][i]];
+                let across = if a == face { b } else { a };
+                assert_eq!(FACE_ADJACENT_FACES[face][i], across, "face {face} edge {i}");
+            }
+        }
+    }
+
+    #[test]
+    fn faces_lie_on_their_planes_facing_their_neighbors() {
+        for face in 0..FACE_COUNT {
+            let normal = FACE_NORMALS[face];
+            let offset = NEIGHBOR_OFFSETS[face].center();
+            assert!(normal.is_normalized());
+            assert!(normal.abs_diff_eq(offset.normalize(), 1e-6), "face {face}");
+            // The face is halfway to the neighbor's center
+            let plane = offset.length() / 2.0;
+            assert!((plane - std::f32::consts::FRAC_1_SQRT_2).abs() < 1e-6);
+            let corners = FACE_VERTICES[face].map(|vertex| CELL_VERTICES[vertex]);
+            for corner in corners {
+                assert!((normal.dot(corner) - plane).abs() < 1e-6, "face {face}");
+            }
+            for i in 0..4 {
+                let [a, b, c] = [0, 1, 2].map(|j| corners[(i + j) % 4]);
+                let winding = (b - a).cross(c - b).dot(normal);
+                assert!(
+                    winding > 0.0,
+                    "face {face} isn't counter-clockwise at corner {i}"
+                );
+            }
+        }
+    }
+}
@@ -1,27 +1,120 @@
-use bevy::{ecs::system::EntityCommands, prelude::*};
+use std::any::TypeId;
+
+use bevy::{
+    asset::Asset,
+    ecs::system::EntityCommands,
+    prelude::*,
+    render::{
+        mesh::Indices,
+        render_resource::{Face, VertexFormat},
+    },
+};
 use bevy_inspector_egui::egui;
+use bevy_trait_query::RegisterExt;
 
-use crate::ShowInUIProperties;
+use crate::{
+    add_primitive_mesh, snap_rotation_to_grid, snap_transform_to_grid, CellFaceColors,
+    EditorHistory, MeshPrimitive, MeshPrimitives, RhombicCoord, SceneComponent, SceneSaver,
+    SceneVisibility, ShowInUIProperties, TransformGizmo, NEIGHBOR_OFFSETS,
+};
 
+/// A component that can be inspected and edited in the Entities window.
+///
+/// The window calls `show_ui` on a copy of the component made with `clone_boxed`, so that the
+/// real `world` can be borrowed alongside it to look up assets, resources and other entities.
+/// If `show_ui` returns `true` the copy is written back to the entity with `clone_onto`.
 #[bevy_trait_query::queryable]
-pub trait DisplayableComponent {
+pub trait DisplayableComponent: Send + Sync {
+    fn component_name() -> &'static str
+    where
+        Self: Sized;
+    /// What the Entities window's "Add Component" menu inserts.
+    fn create_default(world: &mut World) -> Self
+    where
+        Self: Sized;
     fn get_name(&self) -> &'static str;
+    fn clone_boxed(&self) -> Box<dyn DisplayableComponent>;
     fn clone_onto(&self, commands: &mut EntityCommands);
     fn remove_component(&mut self, commands: &mut EntityCommands);
+    fn save(&self, saver: &mut SceneSaver) -> SceneComponent;
     fn show_ui(
         &mut self,
         entity: Entity,
         ui_properties: &mut ShowInUIProperties,
         ui: &mut egui::Ui,
         world: &mut World,
-    );
+    ) -> bool;
+}
+
+/// Every component type registered with
+/// [`RegisterDisplayableComponentExt::register_displayable_component`].
+#[derive(Resource, Default)]
+pub struct DisplayableComponentRegistry {
+    registrations: Vec<DisplayableComponentRegistration>,
+}
+
+#[derive(Clone, Copy)]
+pub struct DisplayableComponentRegistration {
+    pub name: &'static str,
+    pub type_id: TypeId,
+    pub is_on_entity: fn(&World, Entity) -> bool,
+    pub insert_default: fn(&mut World, Entity),
+}
+
+impl DisplayableComponentRegistry {
+    pub fn iter(&self) -> impl Iterator<Item = &DisplayableComponentRegistration> {
+        self.registrations.iter()
+    }
+}
+
+pub trait RegisterDisplayableComponentExt {
+    /// Registers `T` for `dyn DisplayableComponent` trait queries, and records how to add it to
+    /// an entity.
+    fn register_displayable_component<T: DisplayableComponent + Component>(&mut self) -> &mut Self;
+}
+
+impl RegisterDisplayableComponentExt for App {
+    fn register_displayable_component<T: DisplayableComponent + Component>(&mut self) -> &mut Self {
+        self.register_component_as::<dyn DisplayableComponent, T>();
+        self.init_resource::<DisplayableComponentRegistry>();
+        let mut registry = self.world.resource_mut::<DisplayableComponentRegistry>();
+        if registry
+            .iter()
+            .all(|registration| registration.name != T::component_name())
+        {
+            registry
+                .registrations
+                .push(DisplayableComponentRegistration {
+                    name: T::component_name(),
+                    type_id: TypeId::of::<T>(),
+                    is_on_entity: |world, entity| world.get::<T>(entity).is_some(),
+                    insert_default: |world, entity| {
+                        let component = T::create_default(world);
+                        world.entity_mut(entity).insert(component);
+                    },
+                });
+        }
+        self
+    }
 }
 
 impl DisplayableComponent for Transform {
-    fn get_name(&self) -> &'static str {
+    fn component_name() -> &'static str {
         "Transform"
     }
 
+    fn create_default(_world: &mut World) -> Self {
+        Transform::default()
+    }
+
+    fn get_name(&self) -> &'static str {
+        Self::component_name()
+    }
+
+    fn clone_boxed(&self) -> Box<dyn DisplayableComponent> {
+        Box::new(*self)
+    }
+
     fn clone_onto(&self, commands: &mut EntityCommands) {
         commands.insert(*self);
     }
@@ -30,22 +123,50 @@ impl DisplayableComponent for Transform {
         commands.remove::<Self>();
     }
 
+    fn save(&self, _saver: &mut SceneSaver) -> SceneComponent {
+        SceneComponent::Transform {
+            translation: self.translation.to_array(),
+            rotation: self.rotation.to_array(),
+            scale: self.scale.to_array(),
+        }
+    }
+
     fn show_ui(
         &mut self,
         _entity: Entity,
         ui_properties: &mut ShowInUIProperties,
         ui: &mut egui::Ui,
-        _world: &mut World,
-    ) {
-        render_transform_fields(self, ui_properties, ui, true);
+        world: &mut World,
+    ) -> bool {
+        let previous = *self;
+        let grid_snap = world
+            .get_resource::<TransformGizmo>()
+            .is_some_and(|gizmo| gizmo.grid_snap);
+        render_transform_fields(self, ui_properties, ui, true, grid_snap);
+        if grid_snap && ui.button("Snap to Grid").clicked() {
+            *self = snap_transform_to_grid(*self);
+        }
+        *self != previous
     }
 }
 
 impl DisplayableComponent for GlobalTransform {
-    fn get_name(&self) -> &'static str {
+    fn component_name() -> &'static str {
         "Global Transform"
     }
 
+    fn create_default(_world: &mut World) -> Self {
+        GlobalTransform::default()
+    }
+
+    fn get_name(&self) -> &'static str {
+        Self::component_name()
+    }
+
+    fn clone_boxed(&self) -> Box<dyn DisplayableComponent> {
+        Box::new(*self)
+    }
+
     fn clone_onto(&self, commands: &mut EntityCommands) {
         commands.insert(*self);
     }
@@ -54,14 +175,25 @@ impl DisplayableComponent for GlobalTransform {
         commands.remove::<Self>();
     }
 
+    fn save(&self, _saver: &mut SceneSaver) -> SceneComponent {
+        SceneComponent::GlobalTransform
+    }
+
     fn show_ui(
         &mut self,
         _entity: Entity,
         ui_properties: &mut ShowInUIProperties,
         ui: &mut egui::Ui,
         _world: &mut World,
-    ) {
-        render_transform_fields(&mut self.compute_transform(), ui_properties, ui, false);
+    ) -> bool {
+        render_transform_fields(
+            &mut self.compute_transform(),
+            ui_properties,
+            ui,
+            false,
+            false,
+        );
+        false
     }
 }
 
@@ -70,25 +202,49 @@ fn render_transform_fields(
     ui_properties: &mut ShowInUIProperties,
     ui: &mut egui::Ui,
     enabled: bool,
+    grid_snap: bool,
 ) {
     ui.add_enabled_ui(enabled, |ui| {
         ui.horizontal(|ui| {
             ui.label("Position: ");
-            ui.add(
-                egui::DragValue::new(&mut transform.translation.x)
+            let mut translation = transform.translation;
+            // Snapping every frame would stop a drag from ever leaving the cell, so the unsnapped
+            // position is kept until the fields are let go
+            let translation = if enabled && grid_snap {
+                ui_properties.translation_cache.get_or_insert(translation)
+            } else {
+                &mut translation
+            };
+
+            let x_response = ui.add(
+                egui::DragValue::new(&mut translation.x)
                     .prefix("x: ")
                     .speed(0.01),
             );
-            ui.add(
-                egui::DragValue::new(&mut transform.translation.y)
+            let y_response = ui.add(
+                egui::DragValue::new(&mut translation.y)
                     .prefix("y: ")
                     .speed(0.01),
             );
-            ui.add(
-                egui::DragValue::new(&mut transform.translation.z)
+            let z_response = ui.add(
+                egui::DragValue::new(&mut translation.z)
                     .prefix("z: ")
                     .speed(0.01),
             );
+            if x_response.changed() || y_response.changed() || z_response.changed() {
+                transform.translation = if grid_snap {
+                    RhombicCoord::from_world(*translation).center()
+                } else {
+                    *translation
+                };
+            }
+            if enabled
+                && ((!x_response.has_focus() && !x_response.dragged())
+                    && (!y_response.has_focus() && !y_response.dragged())
+                    && (!z_response.has_focus() && !z_response.dragged()))
+            {
+                ui_properties.translation_cache = None;
+            }
         });
         ui.horizontal(|ui| {
             ui.label("Rotation: ");
@@ -116,6 +272,9 @@ fn render_transform_fields(
                     euler_angles.x.to_radians(),
                     euler_angles.z.to_radians(),
                 );
+                if grid_snap {
+                    transform.rotation = snap_rotation_to_grid(transform.rotation);
+                }
             }
             if enabled
                 && ((!x_response.has_focus() && !x_response.dragged())
@@ -170,10 +329,22 @@ fn render_transform_fields(
 }
 
 impl DisplayableComponent for Handle<Mesh> {
-    fn get_name(&self) -> &'static str {
+    fn component_name() -> &'static str {
         "Mesh"
     }
 
+    fn create_default(world: &mut World) -> Self {
+        add_primitive_mesh(world, MeshPrimitive::Cube { size: 1.0 })
+    }
+
+    fn get_name(&self) -> &'static str {
+        Self::component_name()
+    }
+
+    fn clone_boxed(&self) -> Box<dyn DisplayableComponent> {
+        Box::new(self.clone())
+    }
+
     fn clone_onto(&self, commands: &mut EntityCommands) {
         commands.insert(self.clone());
     }
@@ -182,21 +353,227 @@ impl DisplayableComponent for Handle<Mesh> {
         commands.remove::<Self>();
     }
 
+    fn save(&self, saver: &mut SceneSaver) -> SceneComponent {
+        SceneComponent::Mesh(saver.mesh(self))
+    }
+
     fn show_ui(
         &mut self,
-        _entity: Entity,
+        entity: Entity,
         _ui_properties: &mut ShowInUIProperties,
-        _ui: &mut egui::Ui,
-        _world: &mut World,
-    ) {
+        ui: &mut egui::Ui,
+        world: &mut World,
+    ) -> bool {
+        let mut primitive = world.resource::<MeshPrimitives>().get(self);
+        let mut changed = render_asset_users(self, ui, world);
+        // Made unique, and the copy has the same parameters
+        if changed {
+            if let Some(primitive) = primitive {
+                world
+                    .resource_mut::<MeshPrimitives>()
+                    .insert(self, primitive);
+            }
+        }
+        let Some(mesh) = world.resource::<Assets<Mesh>>().get(self) else {
+            ui.label("Not Loaded");
+            return changed;
+        };
+        render_mesh_info(mesh, ui);
+
+        let mut regenerate = false;
+        ui.horizontal(|ui| {
+            ui.label("Primitive: ");
+            egui::ComboBox::new((entity, "Primitive"), "")
+                .selected_text(primitive.map_or("Custom", |primitive| primitive.name()))
+                .show_ui(ui, |ui| {
+                    for new_primitive in MeshPrimitive::ALL {
+                        let selected = primitive.is_some_and(|selected| {
+                            std::mem::discriminant(&selected)
+                                == std::mem::discriminant(&new_primitive)
+                        });
+                        if ui
+                            .selectable_label(selected, new_primitive.name())
+                            .clicked()
+                            && !selected
+                        {
+                            primitive = Some(new_primitive);
+                            regenerate = true;
+                        }
+                    }
+                });
+        });
+        if let Some(primitive) = &mut primitive {
+            regenerate |= render_mesh_primitive_fields(primitive, ui);
+            if regenerate {
+                // A new asset, so other entities using the mesh keep theirs and the old handle
+                // can be put back by undo
+                *self = add_primitive_mesh(world, *primitive);
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// Shows how many entities share the asset, with a button to give this entity its own copy so
+/// editing it doesn't change the others. Returns whether the handle changed.
+fn render_asset_users<T: Asset + Clone>(
+    handle: &mut Handle<T>,
+    ui: &mut egui::Ui,
+    world: &mut World,
+) -> bool {
+    let users = world
+        .query::<&Handle<T>>()
+        .iter(world)
+        .filter(|other| other.id() == handle.id())
+        .count();
+
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label(format!("Users: {users}"));
+        if ui
+            .add_enabled(users > 1, egui::Button::new("Make Unique"))
+            .clicked()
+        {
+            let mut assets = world.resource_mut::<Assets<T>>();
+            if let Some(asset) = assets.get(handle).cloned() {
+                *handle = assets.add(asset);
+                changed = true;
+            }
+        }
+    });
+    changed
+}
+
+fn render_mesh_info(mesh: &Mesh, ui: &mut egui::Ui) {
+    ui.label(format!("Topology: {:?}", mesh.primitive_topology()));
+    ui.label(format!("Vertices: {}", mesh.count_vertices()));
+    ui.label(match mesh.indices() {
+        Some(Indices::U16(indices)) => format!("Indices: {} (u16)", indices.len()),
+        Some(Indices::U32(indices)) => format!("Indices: {} (u32)", indices.len()),
+        None => "Indices: None".to_string(),
+    });
+
+    ui.label("Attributes: ");
+    ui.indent("Attributes", |ui| {
+        for (id, values) in mesh.attributes() {
+            let name = [
+                Mesh::ATTRIBUTE_POSITION,
+                Mesh::ATTRIBUTE_NORMAL,
+                Mesh::ATTRIBUTE_UV_0,
+                Mesh::ATTRIBUTE_TANGENT,
+                Mesh::ATTRIBUTE_COLOR,
+                Mesh::ATTRIBUTE_JOINT_WEIGHT,
+                Mesh::ATTRIBUTE_JOINT_INDEX,
+            ]
+            .into_iter()
+            .find(|attribute| attribute.id == id)
+            .map_or_else(|| format!("{id:?}"), |attribute| attribute.name.to_string());
+            ui.label(format!("{name} ({:?})", VertexFormat::from(values)));
+        }
+    });
+
+    if let Some(aabb) = mesh.compute_aabb() {
+        ui.add_enabled_ui(false, |ui| {
+            for (label, mut corner) in [("AABB Min: ", aabb.min()), ("AABB Max: ", aabb.max())] {
+                ui.horizontal(|ui| {
+                    ui.label(label);
+                    ui.add(egui::DragValue::new(&mut corner.x).prefix("x: "));
+                    ui.add(egui::DragValue::new(&mut corner.y).prefix("y: "));
+                    ui.add(egui::DragValue::new(&mut corner.z).prefix("z: "));
+                });
+            }
+        });
     }
 }
 
+fn render_mesh_primitive_fields(primitive: &mut MeshPrimitive, ui: &mut egui::Ui) -> bool {
+    let mut changed = false;
+    let mut field = |ui: &mut egui::Ui, label: &str, value: egui::DragValue| {
+        ui.horizontal(|ui| {
+            ui.label(label);
+            changed |= ui.add(value).changed();
+        });
+    };
+    match primitive {
+        MeshPrimitive::Plane { size } | MeshPrimitive::Cube { size } => {
+            field(
+                ui,
+                "Size: ",
+                egui::DragValue::new(size)
+                    .speed(0.01)
+                    .clamp_range(0.01..=f32::MAX),
+            );
+        }
+        MeshPrimitive::Sphere {
+            radius,
+            sectors,
+            stacks,
+        } => {
+            field(
+                ui,
+                "Radius: ",
+                egui::DragValue::new(radius)
+                    .speed(0.01)
+                    .clamp_range(0.01..=f32::MAX),
+            );
+            field(
+                ui,
+                "Sectors: ",
+                egui::DragValue::new(sectors).clamp_range(3..=256),
+            );
+            field(
+                ui,
+                "Stacks: ",
+                egui::DragValue::new(stacks).clamp_range(2..=256),
+            );
+        }
+        MeshPrimitive::RhombicDodecahedron { size, bevel, inset } => {
+            field(
+                ui,
+                "Size: ",
+                egui::DragValue::new(size)
+                    .speed(0.01)
+                    .clamp_range(0.01..=f32::MAX),
+            );
+            field(
+                ui,
+                "Bevel: ",
+                egui::DragValue::new(bevel)
+                    .speed(0.005)
+                    .clamp_range(0.0..=f32::MAX),
+            );
+            field(
+                ui,
+                "Inset: ",
+                egui::DragValue::new(inset)
+                    .speed(0.005)
+                    .clamp_range(0.0..=f32::MAX),
+            );
+        }
+    }
+    changed
+}
+
 impl DisplayableComponent for Handle<StandardMaterial> {
-    fn get_name(&self) -> &'static str {
+    fn component_name() -> &'static str {
         "StandardMaterial"
     }
 
+    fn create_default(world: &mut World) -> Self {
+        world
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(StandardMaterial::default())
+    }
+
+    fn get_name(&self) -> &'static str {
+        Self::component_name()
+    }
+
+    fn clone_boxed(&self) -> Box<dyn DisplayableComponent> {
+        Box::new(self.clone())
+    }
+
     fn clone_onto(&self, commands: &mut EntityCommands) {
         commands.insert(self.clone());
     }
@@ -205,21 +582,180 @@ impl DisplayableComponent for Handle<StandardMaterial> {
         commands.remove::<Self>();
     }
 
+    fn save(&self, saver: &mut SceneSaver) -> SceneComponent {
+        SceneComponent::Material(saver.material(self))
+    }
+
     fn show_ui(
         &mut self,
-        _entity: Entity,
+        entity: Entity,
         _ui_properties: &mut ShowInUIProperties,
-        _ui: &mut egui::Ui,
-        _world: &mut World,
-    ) {
+        ui: &mut egui::Ui,
+        world: &mut World,
+    ) -> bool {
+        let changed = render_asset_users(self, ui, world);
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        let Some(material) = materials.get(self) else {
+            ui.label("Not Loaded");
+            return changed;
+        };
+        let mut material = material.clone();
+        if render_material_fields(&mut material, entity, ui) {
+            // The asset is edited in place rather than the component, so it's recorded here
+            let before = std::mem::replace(materials.get_mut(self).unwrap(), material.clone());
+            if let Some(mut history) = world.get_resource_mut::<EditorHistory>() {
+                history.push_material_edit(self.clone(), before, material);
+            }
+        }
+        changed
+    }
+}
+
+fn render_material_fields(
+    material: &mut StandardMaterial,
+    entity: Entity,
+    ui: &mut egui::Ui,
+) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("Base Color: ");
+        // egui's color pickers take linear colors
+        let mut base_color = material.base_color.as_linear_rgba_f32();
+        if ui
+            .color_edit_button_rgba_unmultiplied(&mut base_color)
+            .changed()
+        {
+            let [r, g, b, a] = base_color;
+            material.base_color = Color::rgba_linear(r, g, b, a);
+            changed = true;
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Emissive: ");
+        let [r, g, b, _] = material.emissive.as_linear_rgba_f32();
+        let mut emissive = [r, g, b];
+        if ui.color_edit_button_rgb(&mut emissive).changed() {
+            material.emissive = Color::rgb_linear(emissive[0], emissive[1], emissive[2]);
+            changed = true;
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Metallic: ");
+        changed |= ui
+            .add(egui::Slider::new(&mut material.metallic, 0.0..=1.0))
+            .changed();
+    });
+    ui.horizontal(|ui| {
+        ui.label("Roughness: ");
+        // Bevy clamps anything lower than this
+        changed |= ui
+            .add(egui::Slider::new(
+                &mut material.perceptual_roughness,
+                0.089..=1.0,
+            ))
+            .changed();
+    });
+    ui.horizontal(|ui| {
+        ui.label("Reflectance: ");
+        changed |= ui
+            .add(egui::Slider::new(&mut material.reflectance, 0.0..=1.0))
+            .changed();
+    });
+    ui.horizontal(|ui| {
+        ui.label("Alpha Mode: ");
+        let previous = material.alpha_mode;
+        egui::ComboBox::new((entity, "Alpha Mode"), "")
+            .selected_text(alpha_mode_name(material.alpha_mode))
+            .show_ui(ui, |ui| {
+                for alpha_mode in [
+                    AlphaMode::Opaque,
+                    AlphaMode::Mask(0.5),
+                    AlphaMode::Blend,
+                    AlphaMode::Premultiplied,
+                    AlphaMode::Add,
+                    AlphaMode::Multiply,
+                ] {
+                    // Compared by variant so picking Mask again keeps its cutoff
+                    let selected = std::mem::discriminant(&material.alpha_mode)
+                        == std::mem::discriminant(&alpha_mode);
+                    if ui
+                        .selectable_label(selected, alpha_mode_name(alpha_mode))
+                        .clicked()
+                        && !selected
+                    {
+                        material.alpha_mode = alpha_mode;
+                    }
+                }
+            });
+        if let AlphaMode::Mask(cutoff) = &mut material.alpha_mode {
+            ui.add(
+                egui::DragValue::new(cutoff)
+                    .prefix("cutoff: ")
+                    .speed(0.01)
+                    .clamp_range(0.0..=1.0),
+            );
+        }
+        changed |= material.alpha_mode != previous;
+    });
+    changed |= ui.checkbox(&mut material.unlit, "Unlit").changed();
+    changed |= ui
+        .checkbox(&mut material.double_sided, "Double Sided")
+        .changed();
+    ui.horizontal(|ui| {
+        ui.label("Cull Mode: ");
+        let previous = material.cull_mode;
+        egui::ComboBox::new((entity, "Cull Mode"), "")
+            .selected_text(cull_mode_name(material.cull_mode))
+            .show_ui(ui, |ui| {
+                for cull_mode in [None, Some(Face::Front), Some(Face::Back)] {
+                    ui.selectable_value(
+                        &mut material.cull_mode,
+                        cull_mode,
+                        cull_mode_name(cull_mode),
+                    );
+                }
+            });
+        changed |= material.cull_mode != previous;
+    });
+    changed
+}
+
+fn alpha_mode_name(alpha_mode: AlphaMode) -> &'static str {
+    match alpha_mode {
+        AlphaMode::Opaque => "Opaque",
+        AlphaMode::Mask(_) => "Mask",
+        AlphaMode::Blend => "Blend",
+        AlphaMode::Premultiplied => "Premultiplied",
+        AlphaMode::Add => "Add",
+        AlphaMode::Multiply => "Multiply",
+    }
+}
+
+fn cull_mode_name(cull_mode: Option<Face>) -> &'static str {
+    match cull_mode {
+        None => "None",
+        Some(Face::Front) => "Front",
+        Some(Face::Back) => "Back",
     }
 }
 
 impl DisplayableComponent for Visibility {
-    fn get_name(&self) -> &'static str {
+    fn component_name() -> &'static str {
         "Visibility"
     }
 
+    fn create_default(_world: &mut World) -> Self {
+        Visibility::default()
+    }
+
+    fn get_name(&self) -> &'static str {
+        Self::component_name()
+    }
+
+    fn clone_boxed(&self) -> Box<dyn DisplayableComponent> {
+        Box::new(*self)
+    }
+
     fn clone_onto(&self, commands: &mut EntityCommands) {
         commands.insert(*self);
     }
@@ -228,13 +764,22 @@ impl DisplayableComponent for Visibility {
         commands.remove::<Self>();
     }
 
+    fn save(&self, _saver: &mut SceneSaver) -> SceneComponent {
+        SceneComponent::Visibility(match self {
+            Visibility::Inherited => SceneVisibility::Inherited,
+            Visibility::Hidden => SceneVisibility::Hidden,
+            Visibility::Visible => SceneVisibility::Visible,
+        })
+    }
+
     fn show_ui(
         &mut self,
         entity: Entity,
         _ui_properties: &mut ShowInUIProperties,
         ui: &mut egui::Ui,
         _world: &mut World,
-    ) {
+    ) -> bool {
+        let previous = *self;
         egui::ComboBox::new(entity, "")
             .selected_text(match self {
                 Visibility::Inherited => "Inherited",
@@ -246,14 +791,27 @@ impl DisplayableComponent for Visibility {
                 ui.selectable_value(self, Visibility::Hidden, "Hidden");
                 ui.selectable_value(self, Visibility::Visible, "Visible");
             });
+        *self != previous
     }
 }
 
 impl DisplayableComponent for ComputedVisibility {
-    fn get_name(&self) -> &'static str {
+    fn component_name() -> &'static str {
         "ComputedVisibility"
     }
 
+    fn create_default(_world: &mut World) -> Self {
+        ComputedVisibility::default()
+    }
+
+    fn get_name(&self) -> &'static str {
+        Self::component_name()
+    }
+
+    fn clone_boxed(&self) -> Box<dyn DisplayableComponent> {
+        Box::new(self.clone())
+    }
+
     fn clone_onto(&self, commands: &mut EntityCommands) {
         commands.insert(self.clone());
     }
@@ -262,17 +820,88 @@ impl DisplayableComponent for ComputedVisibility {
         commands.remove::<Self>();
     }
 
+    fn save(&self, _saver: &mut SceneSaver) -> SceneComponent {
+        SceneComponent::ComputedVisibility
+    }
+
     fn show_ui(
         &mut self,
         _entity: Entity,
         _ui_properties: &mut ShowInUIProperties,
         ui: &mut egui::Ui,
         _world: &mut World,
-    ) {
+    ) -> bool {
         if self.is_visible() {
             ui.label("Visible");
         } else {
             ui.label("Not Visible");
         }
+        false
+    }
+}
+
+impl DisplayableComponent for CellFaceColors {
+    fn component_name() -> &'static str {
+        "Cell Face Colors"
+    }
+
+    fn create_default(_world: &mut World) -> Self {
+        CellFaceColors::from_directions()
+    }
+
+    fn get_name(&self) -> &'static str {
+        Self::component_name()
+    }
+
+    fn clone_boxed(&self) -> Box<dyn DisplayableComponent> {
+        Box::new(*self)
+    }
+
+    fn clone_onto(&self, commands: &mut EntityCommands) {
+        commands.insert(*self);
+    }
+
+    fn remove_component(&mut self, commands: &mut EntityCommands) {
+        commands.remove::<Self>();
+    }
+
+    fn save(&self, _saver: &mut SceneSaver) -> SceneComponent {
+        SceneComponent::CellFaceColors(self.0.map(|color| color.as_rgba_f32()))
+    }
+
+    fn show_ui(
+        &mut self,
+        entity: Entity,
+        _ui_properties: &mut ShowInUIProperties,
+        ui: &mut egui::Ui,
+        _world: &mut World,
+    ) -> bool {
+        let previous = *self;
+        egui::Grid::new(("Cell Face Colors", entity)).show(ui, |ui| {
+            for (face, color) in self.0.iter_mut().enumerate() {
+                let offset = NEIGHBOR_OFFSETS[face];
+                ui.label(format!(
+                    "Face {face} ({}, {}, {}): ",
+                    offset.x(),
+                    offset.y(),
+                    offset.z()
+                ));
+                let mut rgba = color.as_linear_rgba_f32();
+                if ui.color_edit_button_rgba_unmultiplied(&mut rgba).changed() {
+                    let [r, g, b, a] = rgba;
+                    *color = Color::rgba_linear(r, g, b, a);
+                }
+                ui.end_row();
+            }
+        });
+        ui.horizontal(|ui| {
+            if ui.button("By Direction").clicked() {
+                *self = CellFaceColors::from_directions();
+            }
+            if ui.button("White").clicked() {
+                *self = CellFaceColors::default();
+            }
+        });
+        *self != previous
     }
 }
@@ -0,0 +1,491 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use bevy::{
+    prelude::*,
+    render::{mesh::VertexAttributeValues, render_resource::PrimitiveTopology},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeshExportFormat {
+    Obj,
+    /// Binary STL.
+    Stl,
+    /// Binary glTF 2.0, a single `.glb` file.
+    Gltf,
+}
+
+impl MeshExportFormat {
+    pub const ALL: [Self; 3] = [Self::Obj, Self::Stl, Self::Gltf];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Obj => "OBJ",
+            Self::Stl => "STL",
+            Self::Gltf => "glTF",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Obj => "obj",
+            Self::Stl => "stl",
+            Self::Gltf => "glb",
+        }
+    }
+
+    pub fn write(self, mesh: &Mesh, writer: impl Write) -> io::Result<()> {
+        match self {
+            Self::Obj => write_obj(mesh, writer),
+            Self::Stl => write_stl(mesh, writer),
+            Self::Gltf => write_glb(mesh, writer),
+        }
+    }
+}
+
+pub fn export_mesh(
+    mesh: &Mesh,
+    format: MeshExportFormat,
+    path: impl AsRef<Path>,
+) -> io::Result<()> {
+    if let Some(parent) = path.as_ref().parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut writer = BufWriter::new(File::create(path)?);
+    format.write(mesh, &mut writer)?;
+    writer.flush()
+}
+
+/// The parts of a triangle list mesh the exporters understand.
+struct ExportData<'a> {
+    positions: &'a [[f32; 3]],
+    normals: Option<&'a [[f32; 3]]>,
+    uvs: Option<&'a [[f32; 2]]>,
+    indices: Vec<u32>,
+}
+
+impl<'a> ExportData<'a> {
+    fn new(mesh: &'a Mesh) -> io::Result<Self> {
+        if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+            return Err(invalid_mesh("only triangle lists can be exported"));
+        }
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            return Err(invalid_mesh("mesh has no Float32x3 positions"));
+        };
+        if positions.is_empty() {
+            return Err(invalid_mesh("mesh has no vertices"));
+        }
+        // Infinite or NaN bounds would make the glTF JSON invalid
+        if !positions
+            .iter()
+            .flatten()
+            .all(|component| component.is_finite())
+        {
+            return Err(invalid_mesh("mesh has positions that aren't finite"));
+        }
+        let normals = match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+            Some(VertexAttributeValues::Float32x3(normals)) => Some(normals.as_slice()),
+            _ => None,
+        };
+        let uvs = match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+            Some(VertexAttributeValues::Float32x2(uvs)) => Some(uvs.as_slice()),
+            _ => None,
+        };
+        if normals.is_some_and(|normals| normals.len() != positions.len())
+            || uvs.is_some_and(|uvs| uvs.len() != positions.len())
+        {
+            return Err(invalid_mesh(
+                "mesh has a different number of normals or UVs than positions",
+            ));
+        }
+        let indices: Vec<u32> = match mesh.indices() {
+            Some(indices) => indices.iter().map(|index| index as u32).collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        if !indices.len().is_multiple_of(3) {
+            return Err(invalid_mesh("mesh has a partial triangle"));
+        }
+        if indices
+            .iter()
+            .any(|&index| index as usize >= positions.len())
+        {
+            return Err(invalid_mesh("mesh has indices past its last vertex"));
+        }
+        Ok(Self {
+            positions,
+            normals,
+            uvs,
+            indices,
+        })
+    }
+
+    fn triangles(&self) -> impl Iterator<Item = [Vec3; 3]> + '_ {
+        self.indices
+            .chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|i| self.positions[triangle[i] as usize].into()))
+    }
+}
+
+fn invalid_mesh(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+pub fn write_obj(mesh: &Mesh, mut writer: impl Write) -> io::Result<()> {
+    let data = ExportData::new(mesh)?;
+    for [x, y, z] in data.positions {
+        writeln!(writer, "v {x} {y} {z}")?;
+    }
+    for [u, v] in data.uvs.into_iter().flatten() {
+        // OBJ texture coordinates start at the bottom left
+        writeln!(writer, "vt {u} {}", 1.0 - v)?;
+    }
+    for [x, y, z] in data.normals.into_iter().flatten() {
+        writeln!(writer, "vn {x} {y} {z}")?;
+    }
+    for triangle in data.indices.chunks_exact(3) {
+        write!(writer, "f")?;
+        for index in triangle {
+            let index = index + 1;
+            match (data.uvs.is_some(), data.normals.is_some()) {
+                (false, false) => write!(writer, " {index}")?,
+                (true, false) => write!(writer, " {index}/{index}")?,
+                (false, true) => write!(writer, " {index}//{index}")?,
+                (true, true) => write!(writer, " {index}/{index}/{index}")?,
+            }
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+pub fn write_stl(mesh: &Mesh, mut writer: impl Write) -> io::Result<()> {
+    let data = ExportData::new(mesh)?;
+    let mut header = [0; 80];
+    header[..16].copy_from_slice(b"rhombic grid stl");
+    writer.write_all(&header)?;
+    writer.write_all(&(data.indices.len() as u32 / 3).to_le_bytes())?;
+    for [a, b, c] in data.triangles() {
+        let normal = (b - a).cross(c - a).normalize_or_zero();
+        for v in [normal, a, b, c] {
+            for component in v.to_array() {
+                writer.write_all(&component.to_le_bytes())?;
+            }
+        }
+        writer.write_all(&0u16.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+pub fn write_glb(mesh: &Mesh, mut writer: impl Write) -> io::Result<()> {
+    const ARRAY_BUFFER: u32 = 34962;
+    const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+    const FLOAT: u32 = 5126;
+    const UNSIGNED_INT: u32 = 5125;
+
+    let data = ExportData::new(mesh)?;
+    let vertex_count = data.positions.len();
+
+    let mut buffer: Vec<u8> = vec![];
+    let mut buffer_views = vec![];
+    let mut add_view = |bytes: Vec<u8>, target: u32| {
+        buffer_views.push(format!(
+            r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":{target}}}"#,
+            buffer.len(),
+            bytes.len(),
+        ));
+        buffer.extend(bytes);
+        buffer_views.len() - 1
+    };
+
+    let (min, max) = data.positions.iter().fold(
+        (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+        |(min, max), &position| (min.min(position.into()), max.max(position.into())),
+    );
+    let mut accessors = vec![format!(
+        r#"{{"bufferView":{},"componentType":{FLOAT},"count":{vertex_count},"type":"VEC3","min":[{},{},{}],"max":[{},{},{}]}}"#,
+        add_view(le_bytes(data.positions.iter().flatten()), ARRAY_BUFFER),
+        min.x,
+        min.y,
+        min.z,
+        max.x,
+        max.y,
+        max.z,
+    )];
+    let mut attributes = vec![r#""POSITION":0"#.to_string()];
+    if let Some(normals) = data.normals {
+        attributes.push(format!(r#""NORMAL":{}"#, accessors.len()));
+        accessors.push(format!(
+            r#"{{"bufferView":{},"componentType":{FLOAT},"count":{vertex_count},"type":"VEC3"}}"#,
+            add_view(le_bytes(normals.iter().flatten()), ARRAY_BUFFER),
+        ));
+    }
+    if let Some(uvs) = data.uvs {
+        attributes.push(format!(r#""TEXCOORD_0":{}"#, accessors.len()));
+        accessors.push(format!(
+            r#"{{"bufferView":{},"componentType":{FLOAT},"count":{vertex_count},"type":"VEC2"}}"#,
+            add_view(le_bytes(uvs.iter().flatten()), ARRAY_BUFFER),
+        ));
+    }
+    let indices = accessors.len();
+    accessors.push(format!(
+        r#"{{"bufferView":{},"componentType":{UNSIGNED_INT},"count":{},"type":"SCALAR"}}"#,
+        add_view(
+            data.indices
+                .iter()
+                .flat_map(|index| index.to_le_bytes())
+                .collect(),
+            ELEMENT_ARRAY_BUFFER,
+        ),
+        data.indices.len(),
+    ));
+
+    let mut json = format!(
+        concat!(
+            r#"{{"asset":{{"version":"2.0","generator":"grid"}},"scene":0,"scenes":[{{"nodes":[0]}}],"#,
+            r#""nodes":[{{"mesh":0}}],"#,
+            r#""meshes":[{{"primitives":[{{"attributes":{{{}}},"indices":{},"mode":4}}]}}],"#,
+            r#""accessors":[{}],"bufferViews":[{}],"buffers":[{{"byteLength":{}}}]}}"#,
+        ),
+        attributes.join(","),
+        indices,
+        accessors.join(","),
+        buffer_views.join(","),
+        buffer.len(),
+    )
+    .into_bytes();
+
+    // Both chunks have to be 4 byte aligned, JSON padded with spaces and binary with zeros
+    json.resize(json.len().next_multiple_of(4), b' ');
+    buffer.resize(buffer.len().next_multiple_of(4), 0);
+
+    let total_length = 12 + 8 + json.len() + 8 + buffer.len();
+    writer.write_all(b"glTF")?;
+    writer.write_all(&2u32.to_le_bytes())?;
+    writer.write_all(&(total_length as u32).to_le_bytes())?;
+    writer.write_all(&(json.len() as u32).to_le_bytes())?;
+    writer.write_all(b"JSON")?;
+    writer.write_all(&json)?;
+    writer.write_all(&(buffer.len() as u32).to_le_bytes())?;
+    writer.write_all(b"BIN\0")?;
+    writer.write_all(&buffer)?;
+    Ok(())
+}
+
+fn le_bytes<'a>(values: impl Iterator<Item = &'a f32>) -> Vec<u8> {
+    values.flat_map(|value| value.to_le_bytes()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::Indices;
+
+    use super::*;
+    use crate::{
+        mesh_chunk, rhombic_dodecahedron, RhombicCoord, RhombicDodecahedronBuilder, RhombicGrid,
+    };
+
+    /// Meshes with and without indices, normals and UVs, with their vertex and triangle counts.
+    fn meshes() -> Vec<(Mesh, usize, usize)> {
+        let mut grid = RhombicGrid::new();
+        for cell in RhombicCoord::new(8, 8, 8).range(2) {
+            grid.set(cell, ());
+        }
+        let chunk = mesh_chunk(&grid, IVec3::ZERO);
+        let chunk_counts = (chunk.positions.len(), chunk.triangle_count());
+        vec![
+            (rhombic_dodecahedron(), 72, 24),
+            (RhombicDodecahedronBuilder::new().build(), 48, 24),
+            (
+                RhombicDodecahedronBuilder::new()
+                    .shared_vertices(true)
+                    .build(),
+                14,
+                24,
+            ),
+            (chunk.into(), chunk_counts.0, chunk_counts.1),
+        ]
+    }
+
+    fn write(format: MeshExportFormat, mesh: &Mesh) -> Vec<u8> {
+        let mut bytes = vec![];
+        format.write(mesh, &mut bytes).unwrap();
+        bytes
+    }
+
+    fn numbers<T: std::str::FromStr>(line: &str) -> Vec<T>
+    where
+        T::Err: std::fmt::Debug,
+    {
+        line.split_whitespace()
+            .skip(1)
+            .map(|number| number.parse().unwrap())
+            .collect()
+    }
+
+    fn f32s(bytes: &[u8]) -> Vec<f32> {
+        bytes
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn obj_round_trip() {
+        for (mesh, vertices, triangles) in meshes() {
+            let data = ExportData::new(&mesh).unwrap();
+            let obj = String::from_utf8(write(MeshExportFormat::Obj, &mesh)).unwrap();
+            let lines =
+                |prefix: &'static str| obj.lines().filter(move |line| line.starts_with(prefix));
+
+            let positions = lines("v ")
+                .map(|line| <[f32; 3]>::try_from(numbers::<f32>(line)).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(positions.len(), vertices);
+            assert_eq!(positions, data.positions);
+            let normals = lines("vn ")
+                .map(|line| <[f32; 3]>::try_from(numbers::<f32>(line)).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(normals, data.normals.unwrap_or_default());
+            let uvs = lines("vt ")
+                .map(|line| {
+                    let [u, v] = <[f32; 2]>::try_from(numbers::<f32>(line)).unwrap();
+                    [u, 1.0 - v]
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(uvs.len(), data.uvs.map_or(0, <[_]>::len));
+            for (uv, expected) in uvs.iter().zip(data.uvs.into_iter().flatten()) {
+                assert!(Vec2::from(*uv).abs_diff_eq(Vec2::from(*expected), 1e-6));
+            }
+
+            let faces = lines("f ").collect::<Vec<_>>();
+            assert_eq!(faces.len(), triangles);
+            let indices = faces
+                .iter()
+                .flat_map(|face| face.split_whitespace().skip(1))
+                .map(|corner| {
+                    // Every corner uses the same index for its position, UV and normal
+                    let mut parts = corner.split('/').filter(|part| !part.is_empty());
+                    let index = parts.next().unwrap().parse::<u32>().unwrap();
+                    assert!(parts.all(|part| part.parse::<u32>().unwrap() == index));
+                    index - 1
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(indices, data.indices);
+        }
+    }
+
+    #[test]
+    fn stl_round_trip() {
+        for (mesh, _, triangles) in meshes() {
+            let data = ExportData::new(&mesh).unwrap();
+            let stl = write(MeshExportFormat::Stl, &mesh);
+            let count = u32::from_le_bytes(stl[80..84].try_into().unwrap()) as usize;
+            assert_eq!(count, triangles);
+            assert_eq!(stl.len(), 84 + triangles * 50);
+            for (triangle, expected) in stl[84..].chunks_exact(50).zip(data.triangles()) {
+                let values = f32s(&triangle[..48]);
+                let [normal, a, b, c] =
+                    [0, 1, 2, 3].map(|i| Vec3::from_slice(&values[i * 3..i * 3 + 3]));
+                assert_eq!([a, b, c], expected);
+                // Counter-clockwise triangles face along their normal
+                assert!((b - a).cross(c - a).dot(normal) > 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn glb_round_trip() {
+        for (mesh, vertices, triangles) in meshes() {
+            let data = ExportData::new(&mesh).unwrap();
+            let glb = write(MeshExportFormat::Gltf, &mesh);
+            let u32_at = |offset: usize| {
+                u32::from_le_bytes(glb[offset..offset + 4].try_into().unwrap()) as usize
+            };
+            assert_eq!(&glb[0..4], b"glTF");
+            assert_eq!(u32_at(8), glb.len());
+            let json_length = u32_at(12);
+            assert_eq!(&glb[16..20], b"JSON");
+            let json = std::str::from_utf8(&glb[20..20 + json_length]).unwrap();
+            let binary_length = u32_at(20 + json_length);
+            assert_eq!(&glb[24 + json_length..28 + json_length], b"BIN\0");
+            assert_eq!(28 + json_length + binary_length, glb.len());
+            let binary = &glb[28 + json_length..];
+
+            // The values following each `"key":` in `json`
+            let values = |key: &str| {
+                json.split(&format!(r#""{key}":"#))
+                    .skip(1)
+                    .map(|rest| {
+                        let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap();
+                        rest[..end].parse::<usize>().unwrap()
+                    })
+                    .collect::<Vec<_>>()
+            };
+            // Every accessor has its own view, positions first and indices last
+            let offsets = values("byteOffset");
+            let lengths = values("byteLength");
+            let views = offsets
+                .iter()
+                .zip(&lengths)
+                .map(|(&offset, &length)| &binary[offset..offset + length])
+                .collect::<Vec<_>>();
+            let counts = values("count");
+            assert_eq!(counts.len(), views.len());
+            assert_eq!(counts[0], vertices);
+            assert_eq!(*counts.last().unwrap(), triangles * 3);
+            assert!(counts[..counts.len() - 1]
+                .iter()
+                .all(|&count| count == vertices));
+
+            let positions = f32s(views[0]);
+            assert_eq!(positions, data.positions.concat());
+            let mut attributes = views[1..views.len() - 1].iter();
+            if let Some(normals) = data.normals {
+                assert_eq!(f32s(attributes.next().unwrap()), normals.concat());
+            }
+            if let Some(uvs) = data.uvs {
+                assert_eq!(f32s(attributes.next().unwrap()), uvs.concat());
+            }
+            assert!(attributes.next().is_none());
+            let indices = views[views.len() - 1]
+                .chunks_exact(4)
+                .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+                .collect::<Vec<_>>();
+            assert_eq!(indices, data.indices);
+        }
+    }
+
+    #[test]
+    fn broken_meshes_are_rejected() {
+        let mut out_of_range = rhombic_dodecahedron();
+        out_of_range.set_indices(Some(Indices::U32(vec![0, 1, 72])));
+        let mut partial = rhombic_dodecahedron();
+        partial.set_indices(Some(Indices::U32(vec![0, 1, 2, 3])));
+        let mut not_finite = rhombic_dodecahedron();
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            not_finite.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+        else {
+            unreachable!()
+        };
+        positions[3][1] = f32::NAN;
+        let mut infinite = Mesh::new(PrimitiveTopology::TriangleList);
+        infinite.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, f32::INFINITY, 0.0]],
+        );
+        let mut too_few_normals = rhombic_dodecahedron();
+        too_few_normals.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 1.0, 0.0]; 3]);
+
+        for mesh in [out_of_range, partial, not_finite, infinite, too_few_normals] {
+            for format in MeshExportFormat::ALL {
+                let error = format.write(&mesh, io::sink()).unwrap_err();
+                assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+            }
+        }
+    }
+}
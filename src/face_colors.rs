@@ -0,0 +1,175 @@
+use bevy::{
+    asset::HandleId,
+    prelude::*,
+    render::mesh::VertexAttributeValues,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    cell_face_vertex_colors, MeshPrimitives, ATTRIBUTE_CELL_FACES, FACE_COUNT, FACE_NORMALS,
+};
+
+/// Colors each face of an entity's cell mesh, one with [`ATTRIBUTE_CELL_FACES`] like those from
+/// [`crate::RhombicDodecahedronBuilder`], by writing vertex colors into the mesh that
+/// [`StandardMaterial`] multiplies with its base color.
+///
+/// Since the colors are written into the mesh asset, the entity is given its own copy of the mesh
+/// the first time they're applied, and other entities that shared it are left as they were.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct CellFaceColors(pub [Color; FACE_COUNT]);
+
+impl CellFaceColors {
+    /// Every face colored by the direction it faces, so the neighbor across it can be told apart.
+    pub fn from_directions() -> Self {
+        Self(FACE_NORMALS.map(|normal| {
+            let color = normal * 0.5 + 0.5;
+            Color::rgb(color.x, color.y, color.z)
+        }))
+    }
+}
+
+impl Default for CellFaceColors {
+    fn default() -> Self {
+        Self([Color::WHITE; FACE_COUNT])
+    }
+}
+
+pub(crate) fn apply_cell_face_colors(
+    mut colored: Query<(Entity, &CellFaceColors, &mut Handle<Mesh>)>,
+    uncolored: Query<&Handle<Mesh>, Without<CellFaceColors>>,
+    mut removed: RemovedComponents<CellFaceColors>,
+    mut events: EventReader<AssetEvent<Mesh>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut primitives: Option<ResMut<MeshPrimitives>>,
+    // The entity each copied mesh was made for
+    mut owners: Local<HashMap<HandleId, Entity>>,
+) {
+    for event in events.iter() {
+        if let AssetEvent::Removed { handle } = event {
+            owners.remove(&handle.id());
+        }
+    }
+
+    // Checked every frame, since the mesh can be regenerated or swapped without the colors
+    // changing
+    for (entity, colors, mut mesh) in &mut colored {
+        let Some(Some(VertexAttributeValues::Uint32(faces))) = meshes
+            .get(&*mesh)
+            .map(|mesh| mesh.attribute(ATTRIBUTE_CELL_FACES))
+        else {
+            continue;
+        };
+        let vertex_colors = cell_face_vertex_colors(faces, &colors.0);
+
+        if owners.get(&mesh.id()) != Some(&entity) {
+            let copy = meshes.get(&*mesh).unwrap().clone();
+            let copy = meshes.add(copy);
+            if let Some(primitives) = &mut primitives {
+                if let Some(primitive) = primitives.get(&mesh) {
+                    primitives.insert(&copy, primitive);
+                }
+            }
+            owners.insert(copy.id(), entity);
+            *mesh = copy;
+        }
+
+        // Writing marks the mesh as changed, which would upload it again every frame
+        let unchanged = match meshes.get(&*mesh).unwrap().attribute(Mesh::ATTRIBUTE_COLOR) {
+            Some(VertexAttributeValues::Float32x4(current)) => *current == vertex_colors,
+            _ => false,
+        };
+        if !unchanged {
+            let mesh = meshes.get_mut(&*mesh).unwrap();
+            mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, vertex_colors);
+        }
+    }
+
+    // Only meshes copied for the entity are cleared, since no one else uses them
+    let mut cleared = HashSet::default();
+    for entity in removed.iter() {
+        let Ok(mesh) = uncolored.get(entity) else {
+            continue;
+        };
+        if owners.get(&mesh.id()) == Some(&entity) && cleared.insert(mesh.id()) {
+            owners.remove(&mesh.id());
+            if let Some(mesh) = meshes.get_mut(mesh) {
+                mesh.remove_attribute(Mesh::ATTRIBUTE_COLOR);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rhombic_dodecahedron;
+
+    fn colors_app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<Mesh>()
+            .add_system(apply_cell_face_colors);
+        app
+    }
+
+    fn vertex_colors(app: &App, entity: Entity) -> Option<Vec<[f32; 4]>> {
+        let mesh = app.world.get::<Handle<Mesh>>(entity).unwrap();
+        match app
+            .world
+            .resource::<Assets<Mesh>>()
+            .get(mesh)
+            .unwrap()
+            .attribute(Mesh::ATTRIBUTE_COLOR)
+        {
+            Some(VertexAttributeValues::Float32x4(colors)) => Some(colors.clone()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn colored_entities_get_their_own_mesh() {
+        let mut app = colors_app();
+        let mesh = app
+            .world
+            .resource_mut::<Assets<Mesh>>()
+            .add(rhombic_dodecahedron());
+        let red = CellFaceColors([Color::RED; FACE_COUNT]);
+        let blue = CellFaceColors([Color::BLUE; FACE_COUNT]);
+        let plain = app.world.spawn(mesh.clone()).id();
+        let first = app.world.spawn((mesh.clone(), red)).id();
+        let second = app.world.spawn((mesh.clone(), blue)).id();
+        app.update();
+
+        assert_eq!(app.world.get::<Handle<Mesh>>(plain), Some(&mesh));
+        assert_eq!(vertex_colors(&app, plain), None);
+        let red_colors = vertex_colors(&app, first).unwrap();
+        let blue_colors = vertex_colors(&app, second).unwrap();
+        assert!(red_colors
+            .iter()
+            .all(|&color| color == [1.0, 0.0, 0.0, 1.0]));
+        assert!(blue_colors
+            .iter()
+            .all(|&color| color == [0.0, 0.0, 1.0, 1.0]));
+
+        // Copying again would change the handle every frame
+        let first_mesh = app.world.get::<Handle<Mesh>>(first).unwrap().clone();
+        app.update();
+        assert_eq!(app.world.get::<Handle<Mesh>>(first), Some(&first_mesh));
+
+        // A duplicate sharing the copy gets a copy of its own
+        let duplicate = app.world.spawn((first_mesh.clone(), blue)).id();
+        app.update();
+        assert_ne!(app.world.get::<Handle<Mesh>>(duplicate), Some(&first_mesh));
+        assert_eq!(vertex_colors(&app, first), Some(red_colors));
+        assert_eq!(vertex_colors(&app, duplicate), Some(blue_colors.clone()));
+
+        // Removing the colors only clears them from the entity's own copy
+        app.world.entity_mut(first).remove::<CellFaceColors>();
+        app.update();
+        assert_eq!(vertex_colors(&app, first), None);
+        assert_eq!(vertex_colors(&app, second), Some(blue_colors.clone()));
+        assert_eq!(vertex_colors(&app, duplicate), Some(blue_colors));
+        assert_eq!(vertex_colors(&app, plain), None);
+    }
+}
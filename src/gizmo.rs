@@ -0,0 +1,352 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{bevy_egui::EguiContexts, egui};
+
+use crate::{
+    snap_rotation_to_grid, EditorCommand, EditorHistory, MainCamera, RhombicCoord, Selection,
+};
+
+/// How close to a handle, in points, the pointer has to be to grab it.
+const GRAB_DISTANCE: f32 = 8.0;
+/// The length of the handles as a fraction of the distance to the camera, so they stay the same
+/// size on screen.
+const GIZMO_SCALE: f32 = 0.15;
+const RING_SEGMENTS: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+impl GizmoMode {
+    pub const ALL: [Self; 3] = [Self::Translate, Self::Rotate, Self::Scale];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Translate => "Translate",
+            Self::Rotate => "Rotate",
+            Self::Scale => "Scale",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GizmoSpace {
+    Local,
+    Global,
+}
+
+/// The handles drawn over the selected entity for moving, rotating and scaling it.
+#[derive(Resource)]
+pub struct TransformGizmo {
+    pub mode: GizmoMode,
+    /// Scaling always happens along the entity's own axes.
+    pub space: GizmoSpace,
+    pub snap: bool,
+    pub translation_snap: f32,
+    /// In degrees.
+    pub rotation_snap: f32,
+    pub scale_snap: f32,
+    /// Snaps moved entities to cell centers and rotated ones to the rotations of a cell, in world
+    /// space.
+    pub grid_snap: bool,
+    hovered_axis: Option<usize>,
+    drag: Option<GizmoDrag>,
+}
+
+impl Default for TransformGizmo {
+    fn default() -> Self {
+        Self {
+            mode: GizmoMode::Translate,
+            space: GizmoSpace::Global,
+            snap: false,
+            translation_snap: 0.25,
+            rotation_snap: 15.0,
+            scale_snap: 0.1,
+            grid_snap: false,
+            hovered_axis: None,
+            drag: None,
+        }
+    }
+}
+
+impl TransformGizmo {
+    /// Whether the pointer is on a handle or dragging one, so clicks shouldn't go to the scene.
+    pub fn is_active(&self) -> bool {
+        self.hovered_axis.is_some() || self.drag.is_some()
+    }
+}
+
+struct GizmoDrag {
+    entity: Entity,
+    axis: usize,
+    start_local: Transform,
+    start_world: Transform,
+    /// Where the handle was grabbed, as a distance along the axis for translating and scaling,
+    /// or a direction in the ring's plane for rotating.
+    start: Vec3,
+}
+
+pub(crate) fn gizmo_idle(gizmo: Res<TransformGizmo>) -> bool {
+    !gizmo.is_active()
+}
+
+pub(crate) fn update_transform_gizmo(
+    mut contexts: EguiContexts,
+    selection: Res<Selection>,
+    mut gizmo: ResMut<TransformGizmo>,
+    mut history: ResMut<EditorHistory>,
+    camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    mut transforms: Query<(&mut Transform, &GlobalTransform, Option<&Parent>)>,
+    parents: Query<&GlobalTransform>,
+) {
+    let ctx = contexts.ctx_mut();
+    gizmo.hovered_axis = None;
+    let selected = selection
+        .entity()
+        .and_then(|entity| Some((entity, transforms.get(entity).ok()?)));
+    let Some((entity, (_, global_transform, parent))) = selected else {
+        gizmo.drag = None;
+        return;
+    };
+    let Ok((camera, camera_transform)) = camera.get_single() else {
+        return;
+    };
+    let parent_matrix = parent
+        .and_then(|parent| parents.get(parent.get()).ok())
+        .map_or(Mat4::IDENTITY, GlobalTransform::compute_matrix);
+
+    let world = match &gizmo.drag {
+        Some(drag) if drag.entity == entity => drag.start_world,
+        _ => global_transform.compute_transform(),
+    };
+    let origin = world.translation;
+    let length = camera_transform.translation().distance(origin) * GIZMO_SCALE;
+    let axes = if gizmo.space == GizmoSpace::Local || gizmo.mode == GizmoMode::Scale {
+        [world.right(), world.up(), world.back()]
+    } else {
+        [Vec3::X, Vec3::Y, Vec3::Z]
+    };
+
+    let to_screen = |position: Vec3| {
+        let viewport_size = camera.logical_viewport_size()?;
+        let position = camera.world_to_viewport(camera_transform, position)?;
+        // Viewport coordinates start at the bottom left, egui's at the top left
+        Some(egui::pos2(position.x, viewport_size.y - position.y))
+    };
+    let pointer_ray = |pointer: egui::Pos2| {
+        let viewport_size = camera.logical_viewport_size()?;
+        camera.viewport_to_world(
+            camera_transform,
+            Vec2::new(pointer.x, viewport_size.y - pointer.y),
+        )
+    };
+
+    // Each handle as a polyline on screen
+    let handles = axes.map(|axis| match gizmo.mode {
+        GizmoMode::Translate | GizmoMode::Scale => [origin, origin + axis * length]
+            .into_iter()
+            .map(to_screen)
+            .collect::<Option<Vec<_>>>(),
+        GizmoMode::Rotate => {
+            let tangent = axis.any_orthonormal_vector();
+            let bitangent = axis.cross(tangent);
+            (0..=RING_SEGMENTS)
+                .map(|i| {
+                    let angle = i as f32 / RING_SEGMENTS as f32 * std::f32::consts::TAU;
+                    to_screen(origin + (tangent * angle.cos() + bitangent * angle.sin()) * length)
+                })
+                .collect::<Option<Vec<_>>>()
+        }
+    });
+
+    let (pointer, pressed, down) = ctx.input(|input| {
+        (
+            input.pointer.hover_pos(),
+            input.pointer.primary_pressed(),
+            input.pointer.primary_down(),
+        )
+    });
+    if gizmo.drag.is_none() && !ctx.is_pointer_over_area() {
+        gizmo.hovered_axis = pointer.and_then(|pointer| {
+            handles
+                .iter()
+                .enumerate()
+                .filter_map(|(axis, handle)| {
+                    Some((axis, distance_to_polyline(pointer, handle.as_ref()?)))
+                })
+                .filter(|&(_, distance)| distance < GRAB_DISTANCE)
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(axis, _)| axis)
+        });
+    }
+
+    if pressed {
+        if let Some(axis) = gizmo.hovered_axis {
+            let start = pointer
+                .and_then(pointer_ray)
+                .and_then(|ray| drag_position(gizmo.mode, ray, origin, axes[axis]));
+            if let Some(start) = start {
+                gizmo.drag = Some(GizmoDrag {
+                    entity,
+                    axis,
+                    start_local: *transforms.get(entity).unwrap().0,
+                    start_world: world,
+                    start,
+                });
+            }
+        }
+    }
+
+    if let Some(drag) = &gizmo.drag {
+        let (mut transform, _, _) = transforms.get_mut(entity).unwrap();
+        if !down || drag.entity != entity {
+            if drag.entity == entity && *transform != drag.start_local {
+                history.push(EditorCommand::EditComponent {
+                    entity,
+                    before: Some(Box::new(drag.start_local)),
+                    after: Some(Box::new(*transform)),
+                });
+            }
+            gizmo.drag = None;
+        } else if let Some(current) = pointer
+            .and_then(pointer_ray)
+            .and_then(|ray| drag_position(gizmo.mode, ray, origin, axes[drag.axis]))
+        {
+            let mut new_world = drag.start_world;
+            let axis = axes[drag.axis];
+            match gizmo.mode {
+                GizmoMode::Translate => {
+                    let mut distance = current.x - drag.start.x;
+                    if gizmo.snap {
+                        distance = snap(distance, gizmo.translation_snap);
+                    }
+                    new_world.translation += axis * distance;
+                    if gizmo.grid_snap {
+                        new_world.translation =
+                            RhombicCoord::from_world(new_world.translation).center();
+                    }
+                }
+                GizmoMode::Rotate => {
+                    let mut angle = axis
+                        .dot(drag.start.cross(current))
+                        .atan2(drag.start.dot(current));
+                    if gizmo.snap {
+                        angle = snap(angle, gizmo.rotation_snap.to_radians());
+                    }
+                    new_world.rotation = Quat::from_axis_angle(axis, angle) * new_world.rotation;
+                    if gizmo.grid_snap {
+                        new_world.rotation = snap_rotation_to_grid(new_world.rotation);
+                    }
+                }
+                GizmoMode::Scale => {
+                    let mut scale = new_world.scale[drag.axis];
+                    if drag.start.x.abs() > f32::EPSILON {
+                        scale *= (current.x / drag.start.x).max(0.01);
+                    }
+                    if gizmo.snap {
+                        scale = snap(scale, gizmo.scale_snap).max(gizmo.scale_snap);
+                    }
+                    new_world.scale[drag.axis] = scale;
+                }
+            }
+            *transform =
+                Transform::from_matrix(parent_matrix.inverse() * new_world.compute_matrix());
+        }
+    }
+
+    let painter = ctx.layer_painter(egui::LayerId::new(
+        egui::Order::Background,
+        egui::Id::new("Transform Gizmo"),
+    ));
+    let active_axis = gizmo
+        .drag
+        .as_ref()
+        .map(|drag| drag.axis)
+        .or(gizmo.hovered_axis);
+    for (axis, handle) in handles.iter().enumerate() {
+        let Some(handle) = handle else {
+            continue;
+        };
+        let color = if active_axis == Some(axis) {
+            egui::Color32::YELLOW
+        } else {
+            [
+                egui::Color32::RED,
+                egui::Color32::GREEN,
+                egui::Color32::BLUE,
+            ][axis]
+        };
+        let stroke = egui::Stroke::new(3.0, color);
+        painter.add(egui::Shape::line(handle.clone(), stroke));
+        let end = *handle.last().unwrap();
+        match gizmo.mode {
+            GizmoMode::Translate => {
+                let direction = (end - handle[0]).normalized() * 12.0;
+                let side = direction.rot90() * 0.5;
+                painter.add(egui::Shape::convex_polygon(
+                    vec![end + direction, end + side, end - side],
+                    color,
+                    egui::Stroke::NONE,
+                ));
+            }
+            GizmoMode::Scale => {
+                painter.rect_filled(
+                    egui::Rect::from_center_size(end, egui::vec2(10.0, 10.0)),
+                    0.0,
+                    color,
+                );
+            }
+            GizmoMode::Rotate => {}
+        }
+    }
+}
+
+/// Where a ray grabs a handle: for translating and scaling, the distance along the axis of the
+/// point on it closest to the ray (in `x`); for rotating, the direction from the origin to where
+/// the ray crosses the ring's plane.
+fn drag_position(mode: GizmoMode, ray: Ray, origin: Vec3, axis: Vec3) -> Option<Vec3> {
+    let direction = ray.direction.normalize();
+    match mode {
+        GizmoMode::Translate | GizmoMode::Scale => {
+            let alignment = axis.dot(direction);
+            let denominator = 1.0 - alignment * alignment;
+            // Looking straight down the axis
+            if denominator < 1e-4 {
+                return None;
+            }
+            let offset = origin - ray.origin;
+            let distance = (alignment * direction.dot(offset) - axis.dot(offset)) / denominator;
+            Some(Vec3::new(distance, 0.0, 0.0))
+        }
+        GizmoMode::Rotate => {
+            let speed = direction.dot(axis);
+            if speed.abs() < 1e-4 {
+                return None;
+            }
+            let point = ray.origin + direction * ((origin - ray.origin).dot(axis) / speed);
+            Some((point - origin).try_normalize()?)
+        }
+    }
+}
+
+fn distance_to_polyline(point: egui::Pos2, polyline: &[egui::Pos2]) -> f32 {
+    polyline
+        .windows(2)
+        .map(|segment| {
+            let (a, b) = (segment[0], segment[1]);
+            let t =
+                ((point - a).dot(b - a) / (b - a).length_sq().max(f32::EPSILON)).clamp(0.0, 1.0);
+            point.distance(a + (b - a) * t)
+        })
+        .fold(f32::INFINITY, f32::min)
+}
+
+fn snap(value: f32, step: f32) -> f32 {
+    if step > 0.0 {
+        (value / step).round() * step
+    } else {
+        value
+    }
+}
@@ -0,0 +1,578 @@
+use std::{any::TypeId, fmt};
+
+use bevy::{pbr::Clusters, prelude::*};
+
+use crate::{
+    apply_commands, DisplayableComponent, DisplayableComponentRegistry, ShowInUIProperties,
+};
+
+/// Everything on an entity and its descendants, enough to bring them back after they're
+/// despawned.
+///
+/// [`DisplayableComponent`]s are cloned, which keeps their assets loaded. Any other component is
+/// copied through reflection, so it has to be registered with its `ReflectComponent`.
+pub struct EntitySnapshot {
+    entity: Entity,
+    /// The parent the entity was a child of, and where in its children.
+    parent: Option<(Entity, usize)>,
+    name: Option<String>,
+    components: Vec<Box<dyn DisplayableComponent>>,
+    reflected_components: Vec<(ReflectComponent, Box<dyn Reflect>)>,
+    children: Vec<EntitySnapshot>,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    NoSuchEntity(Entity),
+    /// A component that isn't displayable or registered for reflection, so can't be copied.
+    UncopyableComponent(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::NoSuchEntity(entity) => write!(f, "entity {entity:?} doesn't exist"),
+            SnapshotError::UncopyableComponent(name) => write!(f, "{name} can't be copied"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl EntitySnapshot {
+    /// Fails rather than leave out a component that can't be copied, so a restored entity is
+    /// never missing anything.
+    pub fn capture(world: &mut World, entity: Entity) -> Result<Self, SnapshotError> {
+        let parent = world.get::<Parent>(entity).map(|parent| {
+            let parent = parent.get();
+            let index = world
+                .get::<Children>(parent)
+                .and_then(|children| children.iter().position(|&child| child == entity))
+                .unwrap_or(0);
+            (parent, index)
+        });
+        let mut snapshot = Self::capture_descendant(world, entity)?;
+        snapshot.parent = parent;
+        Ok(snapshot)
+    }
+
+    fn capture_descendant(world: &mut World, entity: Entity) -> Result<Self, SnapshotError> {
+        let components = world
+            .query::<&dyn DisplayableComponent>()
+            .get(world, entity)
+            .map(|displayable_components| {
+                displayable_components
+                    .iter()
+                    .map(|displayable_component| displayable_component.clone_boxed())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut skipped = vec![
+            TypeId::of::<ShowInUIProperties>(),
+            // Restored by hand, since they refer to other entities
+            TypeId::of::<Parent>(),
+            TypeId::of::<Children>(),
+            // Added back to cameras by bevy_pbr
+            TypeId::of::<Clusters>(),
+        ];
+        if let Some(registry) = world.get_resource::<DisplayableComponentRegistry>() {
+            skipped.extend(registry.iter().map(|registration| registration.type_id));
+        }
+        let entity_ref = world
+            .get_entity(entity)
+            .ok_or(SnapshotError::NoSuchEntity(entity))?;
+        let type_registry = world
+            .get_resource::<AppTypeRegistry>()
+            .map(|registry| registry.read());
+        let mut reflected_components = vec![];
+        for component_id in entity_ref.archetype().components() {
+            let info = world.components().get_info(component_id).unwrap();
+            if info
+                .type_id()
+                .is_some_and(|type_id| skipped.contains(&type_id))
+            {
+                continue;
+            }
+            let reflect_component = info
+                .type_id()
+                .zip(type_registry.as_ref())
+                .and_then(|(type_id, registry)| registry.get_type_data::<ReflectComponent>(type_id))
+                .ok_or_else(|| SnapshotError::UncopyableComponent(info.name().to_string()))?;
+            let component = reflect_component.reflect(entity_ref).unwrap().clone_value();
+            reflected_components.push((reflect_component.clone(), component));
+        }
+        drop(type_registry);
+
+        let name = world
+            .get::<ShowInUIProperties>(entity)
+            .map(|ui_properties| ui_properties.name.clone());
+        let children = world
+            .get::<Children>(entity)
+            .map_or(vec![], |children| children.to_vec())
+            .into_iter()
+            .map(|child| Self::capture_descendant(world, child))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            entity,
+            parent: None,
+            name,
+            components,
+            reflected_components,
+            children,
+        })
+    }
+
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+
+    /// Spawns the entity and its descendants again, keeping their old ids unless something else
+    /// has taken them since. Any that couldn't are added to `remapped` as `(old, new)`.
+    fn restore(&self, world: &mut World, remapped: &mut Vec<(Entity, Entity)>) -> Entity {
+        let entity = match world.get_or_spawn(self.entity) {
+            Some(entity) => entity.id(),
+            None => world.spawn_empty().id(),
+        };
+        if entity != self.entity {
+            remapped.push((self.entity, entity));
+        }
+        apply_commands(world, |commands| {
+            let mut entity_commands = commands.entity(entity);
+            if let Some(name) = &self.name {
+                entity_commands.insert(ShowInUIProperties::new(name.clone()));
+            }
+            for displayable_component in &self.components {
+                displayable_component.clone_onto(&mut entity_commands);
+            }
+        });
+        let mut entity_mut = world.entity_mut(entity);
+        for (reflect_component, component) in &self.reflected_components {
+            reflect_component.insert(&mut entity_mut, &**component);
+        }
+
+        if let Some((parent, index)) = self.parent {
+            if world.get_entity(parent).is_some() {
+                let index = index.min(
+                    world
+                        .get::<Children>(parent)
+                        .map_or(0, |children| children.len()),
+                );
+                world.entity_mut(parent).insert_children(index, &[entity]);
+            }
+        }
+        for child in &self.children {
+            let child = child.restore(world, remapped);
+            world.entity_mut(entity).push_children(&[child]);
+        }
+        entity
+    }
+
+    fn remap(&mut self, old: Entity, new: Entity) {
+        if self.entity == old {
+            self.entity = new;
+        }
+        if let Some((parent, _)) = &mut self.parent {
+            if *parent == old {
+                *parent = new;
+            }
+        }
+        for child in &mut self.children {
+            child.remap(old, new);
+        }
+    }
+}
+
+/// Finds the component called `name` on an entity.
+pub fn component_snapshot(
+    world: &mut World,
+    entity: Entity,
+    name: &str,
+) -> Option<Box<dyn DisplayableComponent>> {
+    let mut query = world.query::<&dyn DisplayableComponent>();
+    let displayable_components = query.get(world, entity).ok()?;
+    let displayable_component = displayable_components
+        .iter()
+        .find(|displayable_component| displayable_component.get_name() == name)?;
+    Some(displayable_component.clone_boxed())
+}
+
+/// One undoable change made in the editor.
+pub enum EditorCommand {
+    /// A component was edited, added or removed. `None` means the entity didn't have it.
+    EditComponent {
+        entity: Entity,
+        before: Option<Box<dyn DisplayableComponent>>,
+        after: Option<Box<dyn DisplayableComponent>>,
+    },
+    /// A material asset was edited, changing every entity that uses it.
+    EditMaterial {
+        material: Handle<StandardMaterial>,
+        before: Box<StandardMaterial>,
+        after: Box<StandardMaterial>,
+    },
+    Spawn(EntitySnapshot),
+    Despawn(EntitySnapshot),
+}
+
+impl EditorCommand {
+    /// Returns the old and new ids of entities that had to be respawned with a different id.
+    fn apply(&mut self, world: &mut World, undo: bool) -> Vec<(Entity, Entity)> {
+        let spawned = matches!(self, Self::Spawn(_));
+        let mut remapped = vec![];
+        match self {
+            Self::EditComponent {
+                entity,
+                before,
+                after,
+            } => {
+                let (from, to) = if undo {
+                    (after, before)
+                } else {
+                    (before, after)
+                };
+                if world.get_entity(*entity).is_some() {
+                    apply_commands(world, |commands| {
+                        let mut entity_commands = commands.entity(*entity);
+                        match (to, from) {
+                            (Some(to), _) => to.clone_onto(&mut entity_commands),
+                            (None, Some(from)) => from.remove_component(&mut entity_commands),
+                            (None, None) => {}
+                        }
+                    });
+                }
+            }
+            Self::EditMaterial {
+                material,
+                before,
+                after,
+            } => {
+                let to = if undo { before } else { after };
+                let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+                if let Some(material) = materials.get_mut(material) {
+                    *material = (**to).clone();
+                }
+            }
+            Self::Spawn(snapshot) | Self::Despawn(snapshot) => {
+                if undo == spawned {
+                    despawn(world, snapshot.entity);
+                } else {
+                    snapshot.restore(world, &mut remapped);
+                }
+            }
+        }
+        remapped
+    }
+
+    fn remap(&mut self, old: Entity, new: Entity) {
+        match self {
+            Self::EditComponent { entity, .. } => {
+                if *entity == old {
+                    *entity = new;
+                }
+            }
+            Self::EditMaterial { .. } => {}
+            Self::Spawn(snapshot) | Self::Despawn(snapshot) => snapshot.remap(old, new),
+        }
+    }
+}
+
+fn despawn(world: &mut World, entity: Entity) {
+    if world.get_entity(entity).is_some() {
+        despawn_with_children_recursive(world, entity);
+    }
+}
+
+/// The undo and redo stacks of the editor.
+#[derive(Resource, Default)]
+pub struct EditorHistory {
+    undo_stack: Vec<EditorCommand>,
+    redo_stack: Vec<EditorCommand>,
+    merging_edit: bool,
+}
+
+impl EditorHistory {
+    pub fn push(&mut self, command: EditorCommand) {
+        self.undo_stack.push(command);
+        self.redo_stack.clear();
+        self.merging_edit = false;
+    }
+
+    /// Records a change made through [`DisplayableComponent::show_ui`]. Changes to the same
+    /// component are merged into one step until [`EditorHistory::end_edit`], so that a whole
+    /// drag is undone at once.
+    pub fn push_edit(
+        &mut self,
+        entity: Entity,
+        before: Box<dyn DisplayableComponent>,
+        after: Box<dyn DisplayableComponent>,
+    ) {
+        if self.merging_edit {
+            if let Some(EditorCommand::EditComponent {
+                entity: last_entity,
+                after: Some(last_after),
+                ..
+            }) = self.undo_stack.last_mut()
+            {
+                if *last_entity == entity && last_after.get_name() == after.get_name() {
+                    *last_after = after;
+                    return;
+                }
+            }
+        }
+        self.push(EditorCommand::EditComponent {
+            entity,
+            before: Some(before),
+            after: Some(after),
+        });
+        self.merging_edit = true;
+    }
+
+    /// Records a change to a material, merged like [`EditorHistory::push_edit`].
+    pub fn push_material_edit(
+        &mut self,
+        material: Handle<StandardMaterial>,
+        before: StandardMaterial,
+        after: StandardMaterial,
+    ) {
+        if self.merging_edit {
+            if let Some(EditorCommand::EditMaterial {
+                material: last_material,
+                after: last_after,
+                ..
+            }) = self.undo_stack.last_mut()
+            {
+                if *last_material == material {
+                    **last_after = after;
+                    return;
+                }
+            }
+        }
+        self.push(EditorCommand::EditMaterial {
+            material,
+            before: Box::new(before),
+            after: Box::new(after),
+        });
+        self.merging_edit = true;
+    }
+
+    /// Records that `entity` was just spawned, or is about to be despawned, with `command` being
+    /// [`EditorCommand::Spawn`] or [`EditorCommand::Despawn`].
+    ///
+    /// If the entity can't be captured the history is cleared instead, since the steps before
+    /// could bring back or change entities that no longer line up with the world.
+    pub fn push_snapshot(
+        world: &mut World,
+        entity: Entity,
+        command: fn(EntitySnapshot) -> EditorCommand,
+    ) {
+        match EntitySnapshot::capture(world, entity) {
+            Ok(snapshot) => world
+                .resource_mut::<EditorHistory>()
+                .push(command(snapshot)),
+            Err(error) => {
+                warn!("Clearing the undo history, since {error}");
+                world.resource_mut::<EditorHistory>().clear();
+            }
+        }
+    }
+
+    /// Ends the edit started by the last [`EditorHistory::push_edit`].
+    pub fn end_edit(&mut self) {
+        self.merging_edit = false;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.merging_edit = false;
+    }
+
+    pub fn undo(world: &mut World) {
+        Self::step(world, true);
+    }
+
+    pub fn redo(world: &mut World) {
+        Self::step(world, false);
+    }
+
+    fn step(world: &mut World, undo: bool) {
+        world.resource_scope(|world, mut history: Mut<EditorHistory>| {
+            let history = history.as_mut();
+            history.merging_edit = false;
+            let (from, to) = if undo {
+                (&mut history.undo_stack, &mut history.redo_stack)
+            } else {
+                (&mut history.redo_stack, &mut history.undo_stack)
+            };
+            let Some(mut command) = from.pop() else {
+                return;
+            };
+            let remapped = command.apply(world, undo);
+            to.push(command);
+            for (old, new) in remapped {
+                for command in history
+                    .undo_stack
+                    .iter_mut()
+                    .chain(history.redo_stack.iter_mut())
+                {
+                    command.remap(old, new);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RegisterDisplayableComponentExt;
+
+    fn history_app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<StandardMaterial>()
+            .init_resource::<EditorHistory>()
+            .register_displayable_component::<Transform>();
+        app
+    }
+
+    fn names(world: &World, entities: &[Entity]) -> Vec<String> {
+        entities
+            .iter()
+            .map(|&entity| world.get::<Name>(entity).unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn undoing_despawn_restores_descendants_and_every_component() {
+        let mut app = history_app();
+        let world = &mut app.world;
+        let grandchild = world.spawn(Name::new("Grandchild")).id();
+        let child = world
+            .spawn((Name::new("Child"), Transform::from_xyz(0.0, 1.0, 0.0)))
+            .push_children(&[grandchild])
+            .id();
+        let entity = world
+            .spawn((
+                Name::new("Entity"),
+                Transform::from_xyz(1.0, 2.0, 3.0),
+                ShowInUIProperties::new("Entity".to_string()),
+            ))
+            .push_children(&[child])
+            .id();
+        let first = world.spawn(Name::new("First")).id();
+        let last = world.spawn(Name::new("Last")).id();
+        let parent = world
+            .spawn(Name::new("Parent"))
+            .push_children(&[first, entity, last])
+            .id();
+
+        EditorHistory::push_snapshot(world, entity, EditorCommand::Despawn);
+        despawn_with_children_recursive(world, entity);
+        assert!(world.get_entity(grandchild).is_none());
+
+        EditorHistory::undo(world);
+        assert_eq!(
+            world.get::<Transform>(entity),
+            Some(&Transform::from_xyz(1.0, 2.0, 3.0))
+        );
+        assert_eq!(
+            world.get::<ShowInUIProperties>(entity).unwrap().name,
+            "Entity"
+        );
+        assert_eq!(
+            names(world, world.get::<Children>(parent).unwrap()),
+            ["First", "Entity", "Last"]
+        );
+        assert_eq!(world.get::<Parent>(child).unwrap().get(), entity);
+        assert_eq!(names(world, &[child, grandchild]), ["Child", "Grandchild"]);
+        assert_eq!(
+            world.get::<Transform>(child),
+            Some(&Transform::from_xyz(0.0, 1.0, 0.0))
+        );
+        assert_eq!(world.get::<Parent>(grandchild).unwrap().get(), child);
+
+        EditorHistory::redo(world);
+        assert!(world.get_entity(entity).is_none());
+        assert!(world.get_entity(grandchild).is_none());
+        assert_eq!(
+            names(world, world.get::<Children>(parent).unwrap()),
+            ["First", "Last"]
+        );
+    }
+
+    #[test]
+    fn uncopyable_entities_clear_the_history() {
+        #[derive(Component)]
+        struct Uncopyable;
+
+        let mut app = history_app();
+        let world = &mut app.world;
+        let entity = world.spawn(Transform::default()).id();
+        EditorHistory::push_snapshot(world, entity, EditorCommand::Spawn);
+        assert!(world.resource::<EditorHistory>().can_undo());
+
+        let child = world.spawn(Uncopyable).id();
+        world.entity_mut(entity).push_children(&[child]);
+        assert!(matches!(
+            EntitySnapshot::capture(world, entity),
+            Err(SnapshotError::UncopyableComponent(_))
+        ));
+        EditorHistory::push_snapshot(world, entity, EditorCommand::Despawn);
+        assert!(!world.resource::<EditorHistory>().can_undo());
+    }
+
+    #[test]
+    fn material_edits_are_merged_and_undone() {
+        let mut app = history_app();
+        let world = &mut app.world;
+        let material = world
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(StandardMaterial::default());
+        let metallic = |world: &World| {
+            world
+                .resource::<Assets<StandardMaterial>>()
+                .get(&material)
+                .unwrap()
+                .metallic
+        };
+        let edit = |world: &mut World, value: f32| {
+            world.resource_scope(|world, mut materials: Mut<Assets<StandardMaterial>>| {
+                let edited = materials.get_mut(&material).unwrap();
+                let before = edited.clone();
+                edited.metallic = value;
+                let after = edited.clone();
+                world.resource_mut::<EditorHistory>().push_material_edit(
+                    material.clone(),
+                    before,
+                    after,
+                );
+            });
+        };
+
+        // A drag, then a separate edit
+        edit(world, 0.25);
+        edit(world, 0.5);
+        world.resource_mut::<EditorHistory>().end_edit();
+        edit(world, 1.0);
+
+        EditorHistory::undo(world);
+        assert_eq!(metallic(world), 0.5);
+        EditorHistory::undo(world);
+        assert_eq!(metallic(world), 0.0);
+        assert!(!world.resource::<EditorHistory>().can_undo());
+        EditorHistory::redo(world);
+        EditorHistory::redo(world);
+        assert_eq!(metallic(world), 1.0);
+    }
+}
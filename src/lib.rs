@@ -1,14 +1,46 @@
-use bevy::{ecs::system::SystemState, prelude::*};
+use bevy::{
+    ecs::system::{CommandQueue, SystemState},
+    input::common_conditions::input_just_pressed,
+    prelude::*,
+};
 use bevy_inspector_egui::{
     bevy_egui::{EguiContexts, EguiPlugin, EguiSet},
     egui,
 };
 
+mod cell_topology;
 mod displayable_component;
+mod export;
+mod face_colors;
+mod gizmo;
+mod history;
+mod mesher;
+mod picking;
+mod raycast;
+mod region;
+mod rhombic_coord;
+mod rhombic_grid;
+mod scene;
+mod symmetry;
 mod utils;
-
-use displayable_component::*;
-use utils::*;
+mod voxel_world;
+
+pub use cell_topology::*;
+pub use displayable_component::*;
+pub use export::*;
+pub use face_colors::*;
+pub use gizmo::*;
+pub use history::*;
+pub use mesher::*;
+pub use picking::*;
+pub use raycast::*;
+pub use region::*;
+pub use rhombic_coord::*;
+pub use rhombic_grid::*;
+pub use scene::*;
+pub use symmetry::*;
+pub use utils::*;
+pub use voxel_world::*;
 
 pub struct GamePlugin;
 
@@ -32,23 +64,48 @@ impl Plugin for GamePlugin {
                 .after(EguiSet::BeginFrame),
         )
         .add_system(camera_controls.in_schedule(CoreSchedule::FixedUpdate))
+        .add_system(stream_voxel_chunks)
+        .add_system(update_voxel_chunk_meshes.after(stream_voxel_chunks))
+        .add_system(update_transform_gizmo.after(EguiSet::BeginFrame))
+        .add_system(
+            pick_selection
+                .run_if(input_just_pressed(MouseButton::Left).and_then(gizmo_idle))
+                .after(update_transform_gizmo),
+        )
+        .add_system(
+            edit_voxels
+                .run_if(input_just_pressed(MouseButton::Right).and_then(gizmo_idle))
+                .before(update_voxel_chunk_meshes),
+        )
+        .add_system(update_selection_highlight.after(pick_selection))
+        .add_system(save_voxel_world_on_exit.in_base_set(CoreSet::Last))
+        .add_system(apply_cell_face_colors)
+        .add_system(forget_removed_mesh_primitives)
         .insert_resource(FixedTime::new(std::time::Duration::from_millis(10)))
         .insert_resource(AmbientLight {
             brightness: 0.05,
             ..default()
         })
+        .init_resource::<VoxelWorld>()
+        .init_resource::<EditorHistory>()
+        .init_resource::<MeshPrimitives>()
+        .init_resource::<Selection>()
+        .init_resource::<TransformGizmo>()
+        .init_resource::<SelectionHighlight>()
         .insert_resource(UISettings {
             settings_window_open: false,
             entities_window_open: false,
+            scene_path: "scene.ron".to_string(),
+            entity_to_delete: None,
         });
 
-        use bevy_trait_query::RegisterExt;
-        app.register_component_as::<dyn DisplayableComponent, Transform>()
-            .register_component_as::<dyn DisplayableComponent, GlobalTransform>()
-            .register_component_as::<dyn DisplayableComponent, Handle<Mesh>>()
-            .register_component_as::<dyn DisplayableComponent, Handle<StandardMaterial>>()
-            .register_component_as::<dyn DisplayableComponent, Visibility>()
-            .register_component_as::<dyn DisplayableComponent, ComputedVisibility>();
+        app.register_displayable_component::<Transform>()
+            .register_displayable_component::<GlobalTransform>()
+            .register_displayable_component::<Handle<Mesh>>()
+            .register_displayable_component::<Handle<StandardMaterial>>()
+            .register_displayable_component::<Visibility>()
+            .register_displayable_component::<ComputedVisibility>()
+            .register_displayable_component::<CellFaceColors>();
     }
 }
 
@@ -56,12 +113,15 @@ impl Plugin for GamePlugin {
 struct UISettings {
     settings_window_open: bool,
     entities_window_open: bool,
+    scene_path: String,
+    entity_to_delete: Option<Entity>,
 }
 
 #[derive(Component)]
 pub struct ShowInUIProperties {
     name: String,
     euler_angles_cache: Option<Vec3>,
+    translation_cache: Option<Vec3>,
 }
 
 impl ShowInUIProperties {
@@ -69,6 +129,7 @@ impl ShowInUIProperties {
         Self {
             name,
             euler_angles_cache: None,
+            translation_cache: None,
         }
     }
 }
@@ -78,6 +139,7 @@ impl Clone for ShowInUIProperties {
         Self {
             name: self.name.clone() + " Copy",
             euler_angles_cache: None,
+            translation_cache: None,
         }
     }
 }
@@ -156,15 +218,104 @@ fn draw_ui(world: &mut World) {
         .ctx_mut()
         .clone();
 
+    if !ctx.wants_keyboard_input() {
+        let input = world.resource::<Input<KeyCode>>();
+        let control = input.any_pressed([KeyCode::LControl, KeyCode::RControl]);
+        let shift = input.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+        if control && input.just_pressed(KeyCode::Y)
+            || control && shift && input.just_pressed(KeyCode::Z)
+        {
+            EditorHistory::redo(world);
+        } else if control && input.just_pressed(KeyCode::Z) {
+            EditorHistory::undo(world);
+        }
+    }
+
     egui::TopBottomPanel::top("Top Panel").show(&ctx, |ui| {
-        let mut settings = world.get_resource_mut::<UISettings>().unwrap();
         ui.horizontal(|ui| {
+            let mut settings = world.get_resource_mut::<UISettings>().unwrap();
             if ui.button("Settings").clicked() {
                 settings.settings_window_open = true;
             }
             if ui.button("Entities").clicked() {
                 settings.entities_window_open = true;
             }
+
+            ui.separator();
+            let history = world.resource::<EditorHistory>();
+            let (can_undo, can_redo) = (history.can_undo(), history.can_redo());
+            if ui
+                .add_enabled(can_undo, egui::Button::new("Undo"))
+                .clicked()
+            {
+                EditorHistory::undo(world);
+            }
+            if ui
+                .add_enabled(can_redo, egui::Button::new("Redo"))
+                .clicked()
+            {
+                EditorHistory::redo(world);
+            }
+
+            ui.separator();
+            let mut gizmo = world.resource_mut::<TransformGizmo>();
+            for mode in GizmoMode::ALL {
+                ui.selectable_value(&mut gizmo.mode, mode, mode.name());
+            }
+            let space = match gizmo.space {
+                GizmoSpace::Local => "Local",
+                GizmoSpace::Global => "Global",
+            };
+            if ui.button(space).clicked() {
+                gizmo.space = match gizmo.space {
+                    GizmoSpace::Local => GizmoSpace::Global,
+                    GizmoSpace::Global => GizmoSpace::Local,
+                };
+            }
+            ui.checkbox(&mut gizmo.snap, "Snap");
+            ui.checkbox(&mut gizmo.grid_snap, "Grid Snap")
+                .on_hover_text("Snap to cell centers and the rotations of a cell");
+
+            ui.separator();
+            let mut settings = world.resource_mut::<UISettings>();
+            ui.label("Scene: ");
+            ui.text_edit_singleline(&mut settings.scene_path);
+            let scene_path = settings.scene_path.clone();
+            if ui.button("Save").clicked() {
+                match save_scene_to_file(world, &scene_path) {
+                    Ok(()) => info!("Saved scene to {scene_path}"),
+                    Err(error) => error!("Failed to save scene to {scene_path}: {error}"),
+                }
+            }
+            if ui
+                .button("Load")
+                .on_hover_text("Replaces every entity in the Entities window")
+                .clicked()
+            {
+                match load_scene_from_file(world, &scene_path) {
+                    Ok(()) => {
+                        // The history refers to entities that don't exist anymore
+                        world.resource_mut::<EditorHistory>().clear();
+                        info!("Loaded scene from {scene_path}");
+                    }
+                    Err(error) => error!("Failed to load scene from {scene_path}: {error}"),
+                }
+            }
+
+            ui.separator();
+            if ui
+                .button("Save World")
+                .on_hover_text(
+                    "Right click to place voxels and shift right click to remove them. \
+                     Changes are also saved when closing.",
+                )
+                .clicked()
+            {
+                match world.resource_mut::<VoxelWorld>().save() {
+                    Ok(()) => info!("Saved voxel world"),
+                    Err(error) => error!("Failed to save voxel world: {error}"),
+                }
+            }
         });
     });
 
@@ -192,6 +343,32 @@ fn draw_ui(world: &mut World) {
                     time_step.period = std::time::Duration::from_secs_f64(step);
                 }
             });
+
+            let mut gizmo = world.resource_mut::<TransformGizmo>();
+            ui.horizontal(|ui| {
+                ui.label("Translation Snap: ");
+                ui.add(
+                    egui::DragValue::new(&mut gizmo.translation_snap)
+                        .speed(0.01)
+                        .clamp_range(0.0..=f32::MAX),
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.label("Rotation Snap: ");
+                ui.add(
+                    egui::DragValue::new(&mut gizmo.rotation_snap)
+                        .suffix("°")
+                        .clamp_range(0.0..=360.0),
+                );
+            });
+            ui.horizontal(|ui| {
+                ui.label("Scale Snap: ");
+                ui.add(
+                    egui::DragValue::new(&mut gizmo.scale_snap)
+                        .speed(0.01)
+                        .clamp_range(0.0..=f32::MAX),
+                );
+            });
             ui.allocate_space(ui.available_size());
         });
     world
@@ -199,6 +376,9 @@ fn draw_ui(world: &mut World) {
         .unwrap()
         .settings_window_open = settings_window_open;
 
+    if world.resource::<Selection>().reveal {
+        world.resource_mut::<UISettings>().entities_window_open = true;
+    }
     let mut entities_window_open = world
         .get_resource_mut::<UISettings>()
         .unwrap()
@@ -207,69 +387,212 @@ fn draw_ui(world: &mut World) {
         .open(&mut entities_window_open)
         .vscroll(true)
         .show(&ctx, |ui| {
+            let mut new_entity = None;
+            ui.menu_button("New Entity", |ui| {
+                for template in NewEntity::all() {
+                    if ui.button(template.name()).clicked() {
+                        new_entity = Some(template);
+                        ui.close_menu();
+                    }
+                }
+            });
+            if let Some(template) = new_entity {
+                // Put it where the camera can see it
+                let transform = world
+                    .query_filtered::<&Transform, With<MainCamera>>()
+                    .get_single(world)
+                    .map_or(Transform::IDENTITY, |camera| {
+                        Transform::from_translation(camera.translation + camera.forward() * 3.0)
+                    });
+                let entity = template.spawn(world, transform);
+                EditorHistory::push_snapshot(world, entity, EditorCommand::Spawn);
+            }
+
             let entities = world
                 .query_filtered::<Entity, With<ShowInUIProperties>>()
                 .iter_mut(world)
                 .collect::<Vec<_>>();
             for entity in entities {
                 let ui_properties = world.get::<ShowInUIProperties>(entity).unwrap();
-                egui::CollapsingHeader::new(&ui_properties.name)
+                let selection = world.resource::<Selection>();
+                let selected = selection.entity() == Some(entity);
+                let reveal = selected && selection.reveal;
+                let mut header_text = egui::RichText::new(&ui_properties.name);
+                if selected {
+                    header_text = header_text
+                        .color(ui.visuals().selection.stroke.color)
+                        .background_color(ui.visuals().selection.bg_fill);
+                }
+                let response = egui::CollapsingHeader::new(header_text)
                     .id_source(entity)
+                    .open(reveal.then_some(true))
                     .show(ui, |ui| {
+                        // Copied out so components can be shown with the whole world borrowed.
+                        // The entity keeps its own meanwhile, so looking it up still finds its name
+                        let properties = world.get::<ShowInUIProperties>(entity).unwrap();
+                        let mut ui_properties = ShowInUIProperties {
+                            name: properties.name.clone(),
+                            euler_angles_cache: properties.euler_angles_cache,
+                            translation_cache: properties.translation_cache,
+                        };
+
                         ui.horizontal(|ui| {
                             ui.label("Name: ");
-                            let mut ui_properties =
-                                world.get_mut::<ShowInUIProperties>(entity).unwrap();
                             ui.text_edit_singleline(&mut ui_properties.name);
                         });
 
-                        {
-                            let mut system_state = SystemState::<(
-                                Commands,
-                                Query<(&mut ShowInUIProperties, &mut dyn DisplayableComponent)>,
-                            )>::from_world(world);
-                            let (mut commands, mut query) = system_state.get_mut(world);
-
-                            let (mut ui_properties, mut displayable_components) =
-                                query.get_mut(entity).unwrap();
-
-                            for mut displayable_component in &mut displayable_components {
-                                let name = displayable_component.get_name();
-                                let mut remove = false;
-                                ui.collapsing(name, |ui| {
-                                    displayable_component.show_ui(
-                                        entity,
-                                        ui_properties.as_mut(),
-                                        ui,
-                                        &mut World::new(), // somehow pass the actual world in here
-                                    );
-                                    remove |= ui.button("Remove").clicked();
+                        let displayable_components = {
+                            let mut system_state =
+                                SystemState::<Query<&dyn DisplayableComponent>>::new(world);
+                            let query = system_state.get(world);
+                            query
+                                .get(entity)
+                                .unwrap()
+                                .iter()
+                                .map(|displayable_component| displayable_component.clone_boxed())
+                                .collect::<Vec<_>>()
+                        };
+
+                        for mut displayable_component in displayable_components {
+                            let name = displayable_component.get_name();
+                            let before = displayable_component.clone_boxed();
+                            let mut changed = false;
+                            let mut remove = false;
+                            ui.collapsing(name, |ui| {
+                                changed = displayable_component.show_ui(
+                                    entity,
+                                    &mut ui_properties,
+                                    ui,
+                                    world,
+                                );
+                                remove = ui.button("Remove").clicked();
+                            });
+                            if changed {
+                                apply_commands(world, |commands| {
+                                    displayable_component.clone_onto(&mut commands.entity(entity));
                                 });
-                                if remove {
-                                    let mut entity_commands = commands.entity(entity);
-                                    displayable_component.remove_component(&mut entity_commands);
-                                }
+                                let after = displayable_component.clone_boxed();
+                                world
+                                    .resource_mut::<EditorHistory>()
+                                    .push_edit(entity, before, after);
+                            }
+                            if remove {
+                                apply_commands(world, |commands| {
+                                    displayable_component
+                                        .remove_component(&mut commands.entity(entity));
+                                });
+                                world.resource_mut::<EditorHistory>().push(
+                                    EditorCommand::EditComponent {
+                                        entity,
+                                        before: Some(displayable_component),
+                                        after: None,
+                                    },
+                                );
                             }
                         }
 
-                        if ui.button("Duplicate").clicked() {
-                            let mut system_state = SystemState::<(
-                                Commands,
-                                Query<(&ShowInUIProperties, &mut dyn DisplayableComponent)>,
-                            )>::from_world(world);
-                            let (mut commands, mut query) = system_state.get_mut(world);
-
-                            let (ui_properties, mut displayable_components) =
-                                query.get_mut(entity).unwrap();
+                        *world.get_mut::<ShowInUIProperties>(entity).unwrap() = ui_properties;
+
+                        let missing_components = world
+                            .resource::<DisplayableComponentRegistry>()
+                            .iter()
+                            .filter(|registration| !(registration.is_on_entity)(world, entity))
+                            .copied()
+                            .collect::<Vec<_>>();
+                        let mut added_component = None;
+                        ui.add_enabled_ui(!missing_components.is_empty(), |ui| {
+                            ui.menu_button("Add Component", |ui| {
+                                for registration in &missing_components {
+                                    if ui.button(registration.name).clicked() {
+                                        added_component = Some(*registration);
+                                        ui.close_menu();
+                                    }
+                                }
+                            });
+                        });
+                        if let Some(registration) = added_component {
+                            (registration.insert_default)(world, entity);
+                            let after = component_snapshot(world, entity, registration.name);
+                            world.resource_mut::<EditorHistory>().push(
+                                EditorCommand::EditComponent {
+                                    entity,
+                                    before: None,
+                                    after,
+                                },
+                            );
+                        }
 
+                        if ui.button("Duplicate").clicked() {
+                            let mut system_state = SystemState::<
+                                Query<(&ShowInUIProperties, &dyn DisplayableComponent)>,
+                            >::new(world);
+                            let query = system_state.get(world);
+                            let (ui_properties, displayable_components) =
+                                query.get(entity).unwrap();
                             let ui_properties = ui_properties.clone();
-
-                            let mut entity_commands = commands.spawn(ui_properties);
-                            for displayable_component in &mut displayable_components {
-                                displayable_component.clone_onto(&mut entity_commands);
+                            let displayable_components = displayable_components
+                                .iter()
+                                .map(|displayable_component| displayable_component.clone_boxed())
+                                .collect::<Vec<_>>();
+
+                            let mut duplicate = None;
+                            apply_commands(world, |commands| {
+                                let mut entity_commands = commands.spawn(ui_properties);
+                                for displayable_component in &displayable_components {
+                                    displayable_component.clone_onto(&mut entity_commands);
+                                }
+                                duplicate = Some(entity_commands.id());
+                            });
+                            if let Some(duplicate) = duplicate {
+                                EditorHistory::push_snapshot(
+                                    world,
+                                    duplicate,
+                                    EditorCommand::Spawn,
+                                );
                             }
                         }
+
+                        if ui.button("Delete").clicked() {
+                            world.resource_mut::<UISettings>().entity_to_delete = Some(entity);
+                        }
+
+                        let mesh = world
+                            .get::<Handle<Mesh>>(entity)
+                            .and_then(|mesh| world.resource::<Assets<Mesh>>().get(mesh));
+                        if let Some(mesh) = mesh {
+                            ui.horizontal(|ui| {
+                                ui.label("Export Mesh: ");
+                                for format in MeshExportFormat::ALL {
+                                    if ui.button(format.name()).clicked() {
+                                        let name =
+                                            &world.get::<ShowInUIProperties>(entity).unwrap().name;
+                                        let file_name = name
+                                            .chars()
+                                            .map(|c| if c.is_alphanumeric() { c } else { '_' })
+                                            .collect::<String>();
+                                        let path =
+                                            format!("exports/{file_name}.{}", format.extension());
+                                        match export_mesh(mesh, format, &path) {
+                                            Ok(()) => info!("Exported mesh to {path}"),
+                                            Err(error) => {
+                                                error!("Failed to export mesh to {path}: {error}")
+                                            }
+                                        }
+                                    }
+                                }
+                            });
+                        }
                     });
+
+                if response.header_response.clicked() {
+                    world.resource_mut::<Selection>().selected = Some(Selected::Entity(entity));
+                }
+                if reveal {
+                    response
+                        .header_response
+                        .scroll_to_me(Some(egui::Align::TOP));
+                    world.resource_mut::<Selection>().reveal = false;
+                }
             }
             ui.allocate_space(ui.available_size());
         });
@@ -277,6 +600,144 @@ fn draw_ui(world: &mut World) {
         .get_resource_mut::<UISettings>()
         .unwrap()
         .entities_window_open = entities_window_open;
+
+    // Like `euler_angles_cache`, an edit lasts while a widget is being dragged or typed in
+    if !ctx.is_using_pointer() && ctx.memory(|memory| memory.focus().is_none()) {
+        world.resource_mut::<EditorHistory>().end_edit();
+    }
+
+    let entity_to_delete = world.resource::<UISettings>().entity_to_delete;
+    if let Some(entity) = entity_to_delete {
+        let name = world
+            .get::<ShowInUIProperties>(entity)
+            .map(|ui_properties| ui_properties.name.clone());
+        let mut close = name.is_none();
+        if let Some(name) = name {
+            egui::Window::new("Delete Entity")
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+                .show(&ctx, |ui| {
+                    ui.label(format!("Delete \"{name}\" and all of its children?"));
+                    if let Err(error) = EntitySnapshot::capture(world, entity) {
+                        ui.label(format!(
+                            "This can't be undone, and clears the undo history, since {error}."
+                        ));
+                    }
+                    ui.horizontal(|ui| {
+                        if ui.button("Delete").clicked() {
+                            EditorHistory::push_snapshot(world, entity, EditorCommand::Despawn);
+                            despawn_with_children_recursive(world, entity);
+                            close = true;
+                        }
+                        if ui.button("Cancel").clicked() {
+                            close = true;
+                        }
+                    });
+                });
+        }
+        if close {
+            world.resource_mut::<UISettings>().entity_to_delete = None;
+        }
+    }
+}
+
+/// The kinds of entity the Entities window can create.
+#[derive(Clone, Copy)]
+enum NewEntity {
+    Empty,
+    Primitive(MeshPrimitive),
+    PointLight,
+    DirectionalLight,
+    SpotLight,
+    Camera,
+}
+
+impl NewEntity {
+    fn all() -> impl Iterator<Item = Self> {
+        std::iter::once(Self::Empty)
+            .chain(MeshPrimitive::ALL.map(Self::Primitive))
+            .chain([
+                Self::PointLight,
+                Self::DirectionalLight,
+                Self::SpotLight,
+                Self::Camera,
+            ])
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Empty => "Empty",
+            Self::Primitive(primitive) => primitive.name(),
+            Self::PointLight => "Point Light",
+            Self::DirectionalLight => "Directional Light",
+            Self::SpotLight => "Spot Light",
+            Self::Camera => "Camera",
+        }
+    }
+
+    fn spawn(self, world: &mut World, transform: Transform) -> Entity {
+        let ui_properties = ShowInUIProperties::new(self.name().to_string());
+        match self {
+            Self::Empty => world.spawn((SpatialBundle::from_transform(transform), ui_properties)),
+            Self::Primitive(primitive) => {
+                let mesh = add_primitive_mesh(world, primitive);
+                let material = world
+                    .resource_mut::<Assets<StandardMaterial>>()
+                    .add(Color::rgb(0.7, 0.7, 0.7).into());
+                world.spawn((
+                    PbrBundle {
+                        mesh,
+                        material,
+                        transform,
+                        ..default()
+                    },
+                    ui_properties,
+                ))
+            }
+            Self::PointLight => world.spawn((
+                PointLightBundle {
+                    transform,
+                    ..default()
+                },
+                ui_properties,
+            )),
+            Self::DirectionalLight => world.spawn((
+                DirectionalLightBundle {
+                    transform,
+                    ..default()
+                },
+                ui_properties,
+            )),
+            Self::SpotLight => world.spawn((
+                SpotLightBundle {
+                    transform,
+                    ..default()
+                },
+                ui_properties,
+            )),
+            Self::Camera => world.spawn((
+                Camera3dBundle {
+                    // Two active cameras would both draw to the window
+                    camera: Camera {
+                        is_active: false,
+                        ..default()
+                    },
+                    transform,
+                    ..default()
+                },
+                ui_properties,
+            )),
+        }
+        .id()
+    }
+}
+
+/// Runs commands against the world right away, rather than at the end of the stage.
+fn apply_commands(world: &mut World, f: impl FnOnce(&mut Commands)) {
+    let mut queue = CommandQueue::default();
+    f(&mut Commands::new(&mut queue, world));
+    queue.apply(world);
 }
 
 fn camera_controls(
@@ -0,0 +1,212 @@
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+    utils::HashMap,
+};
+
+use crate::{chunk_origin, rhombic_face_vertices, RhombicGrid, FACE_COUNT, FACE_NORMALS};
+
+/// The visible faces of one chunk, with positions relative to the chunk origin.
+#[derive(Clone, Debug, Default)]
+pub struct ChunkMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
+}
+
+impl ChunkMesh {
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Each rhombic face is two triangles.
+    pub fn face_count(&self) -> usize {
+        self.triangle_count() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    fn push_vertex(&mut self, position: Vec3, normal: Vec3) -> u32 {
+        self.positions.push(position.to_array());
+        self.normals.push(normal.to_array());
+        self.positions.len() as u32 - 1
+    }
+}
+
+impl From<ChunkMesh> for Mesh {
+    fn from(chunk_mesh: ChunkMesh) -> Self {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, chunk_mesh.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, chunk_mesh.normals);
+        mesh.set_indices(Some(Indices::U32(chunk_mesh.indices)));
+        mesh
+    }
+}
+
+/// Builds the faces of the occupied cells in `chunk` that aren't hidden by an occupied neighbor,
+/// including neighbors in other chunks.
+pub fn mesh_chunk<T>(grid: &RhombicGrid<T>, chunk: IVec3) -> ChunkMesh {
+    build_chunk_mesh(grid, chunk, |mesh, _, position, normal, _| {
+        mesh.push_vertex(position, normal)
+    })
+}
+
+/// Like [`mesh_chunk`], but faces of equal cells that lie in the same plane share vertices.
+///
+/// Coplanar faces can't be merged into larger polygons in this tiling: faces with the same
+/// orientation never share an edge, only the 4-valent vertices at the ends of their long
+/// diagonals. Welding those vertices is the only reduction on offer, and it is a small one, e.g.
+/// 1776 vertices down to 1632 for a ball of radius 3. The surface is identical to
+/// [`mesh_chunk`]'s.
+pub fn mesh_chunk_merged<T: PartialEq>(grid: &RhombicGrid<T>, chunk: IVec3) -> ChunkMesh {
+    // Corners are always on the half-integer lattice, so doubling them gives an exact key
+    let mut welded: HashMap<(IVec3, usize), Vec<(u32, &T)>> = HashMap::default();
+    build_chunk_mesh(grid, chunk, |mesh, face, position, normal, value| {
+        let candidates = welded
+            .entry(((position * 2.0).round().as_ivec3(), face))
+            .or_default();
+        if let Some(&(index, _)) = candidates.iter().find(|(_, other)| *other == value) {
+            return index;
+        }
+        let index = mesh.push_vertex(position, normal);
+        candidates.push((index, value));
+        index
+    })
+}
+
+fn build_chunk_mesh<'a, T>(
+    grid: &'a RhombicGrid<T>,
+    chunk: IVec3,
+    mut vertex: impl FnMut(&mut ChunkMesh, usize, Vec3, Vec3, &'a T) -> u32,
+) -> ChunkMesh {
+    let origin = chunk_origin(chunk).as_vec3();
+    let faces: [_; FACE_COUNT] =
+        std::array::from_fn(|face| (rhombic_face_vertices(face), FACE_NORMALS[face]));
+
+    let mut mesh = ChunkMesh::default();
+    for (cell, value) in grid.iter_chunk(chunk) {
+        let center = cell.center() - origin;
+        for (face, (corners, normal)) in faces.iter().enumerate() {
+            if grid.contains(cell.neighbor(face)) {
+                continue;
+            }
+            let [a, b, c, d] =
+                corners.map(|corner| vertex(&mut mesh, face, center + corner, *normal, value));
+            mesh.indices.extend([a, b, d, b, c, d]);
+        }
+    }
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RhombicCoord;
+
+    #[test]
+    fn lone_cell_has_every_face() {
+        let mut grid = RhombicGrid::new();
+        grid.set(RhombicCoord::new(8, 8, 8), ());
+        let mesh = mesh_chunk(&grid, IVec3::ZERO);
+        assert_eq!(mesh.face_count(), 12);
+        assert_eq!(mesh.positions.len(), 48);
+    }
+
+    #[test]
+    fn neighbors_hide_their_shared_face() {
+        let mut grid = RhombicGrid::new();
+        let cell = RhombicCoord::new(8, 8, 8);
+        grid.set(cell, ());
+        for face in 0..FACE_COUNT {
+            grid.set(cell.neighbor(face), ());
+            assert_eq!(mesh_chunk(&grid, IVec3::ZERO).face_count(), 22);
+            grid.remove(cell.neighbor(face));
+        }
+
+        // Cells only sharing a vertex hide nothing
+        grid.set(cell.second_shell()[0], ());
+        assert_eq!(mesh_chunk(&grid, IVec3::ZERO).face_count(), 24);
+    }
+
+    #[test]
+    fn faces_are_culled_across_chunk_borders() {
+        let mut grid = RhombicGrid::new();
+        let inside = RhombicCoord::new(0, 8, 8);
+        let outside = inside.neighbor(7);
+        assert_eq!(outside, RhombicCoord::new(-1, 9, 8));
+        grid.set(inside, ());
+        grid.set(outside, ());
+        assert_eq!(mesh_chunk(&grid, IVec3::ZERO).face_count(), 11);
+        assert_eq!(mesh_chunk(&grid, IVec3::new(-1, 0, 0)).face_count(), 11);
+
+        grid.remove(outside);
+        assert_eq!(mesh_chunk(&grid, IVec3::ZERO).face_count(), 12);
+        assert!(mesh_chunk(&grid, IVec3::new(-1, 0, 0)).is_empty());
+    }
+
+    fn blob() -> RhombicGrid<u16> {
+        let mut grid = RhombicGrid::new();
+        for cell in RhombicCoord::new(8, 8, 8).range(3) {
+            grid.set(cell, 1);
+        }
+        grid
+    }
+
+    fn triangles(mesh: &ChunkMesh) -> impl Iterator<Item = [Vec3; 3]> + '_ {
+        mesh.indices
+            .chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|i| Vec3::from(mesh.positions[triangle[i] as usize])))
+    }
+
+    fn surface_area(mesh: &ChunkMesh) -> f32 {
+        triangles(mesh)
+            .map(|[a, b, c]| (b - a).cross(c - a).length() / 2.0)
+            .sum()
+    }
+
+    /// Whether every edge is used once in each direction, i.e. by exactly two consistently wound
+    /// triangles.
+    fn is_watertight(mesh: &ChunkMesh) -> bool {
+        let key = |v: Vec3| (v * 2.0).round().as_ivec3().to_array();
+        let mut edges: HashMap<([i32; 3], [i32; 3]), usize> = HashMap::default();
+        for triangle in triangles(mesh) {
+            for i in 0..3 {
+                let edge = (key(triangle[i]), key(triangle[(i + 1) % 3]));
+                *edges.entry(edge).or_default() += 1;
+            }
+        }
+        edges
+            .iter()
+            .all(|(&(a, b), &count)| count == 1 && edges.get(&(b, a)) == Some(&1))
+    }
+
+    #[test]
+    fn welding_keeps_the_surface() {
+        let grid = blob();
+        let naive = mesh_chunk(&grid, IVec3::ZERO);
+        let merged = mesh_chunk_merged(&grid, IVec3::ZERO);
+
+        assert_eq!(merged.face_count(), naive.face_count());
+        assert!((surface_area(&merged) - surface_area(&naive)).abs() < 1e-3);
+        assert!(is_watertight(&naive));
+        assert!(is_watertight(&merged));
+        assert_eq!(naive.positions.len(), 1776);
+        assert_eq!(merged.positions.len(), 1632);
+    }
+
+    #[test]
+    fn welding_keeps_different_values_apart() {
+        let mut grid = blob();
+        for cell in RhombicCoord::new(8, 8, 8).range(3) {
+            if cell.x() > 8 {
+                grid.set(cell, 2);
+            }
+        }
+        let merged = mesh_chunk_merged(&grid, IVec3::ZERO);
+        let uniform = mesh_chunk_merged(&blob(), IVec3::ZERO);
+        assert!(merged.positions.len() > uniform.positions.len());
+        assert!(is_watertight(&merged));
+    }
+}
@@ -0,0 +1,273 @@
+use bevy::{
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    render::{render_resource::PrimitiveTopology, view::NoFrustumCulling},
+    window::PrimaryWindow,
+};
+use bevy_inspector_egui::bevy_egui::EguiContexts;
+
+use crate::{
+    ray_mesh_intersection, MainCamera, RhombicCoord, RhombicRaycast, ShowInUIProperties,
+    VoxelWorld, CELL_VERTICES, EDGE_VERTICES,
+};
+
+/// How far away voxels can be picked.
+const MAX_CELL_PICK_DISTANCE: f32 = 100.0;
+
+/// The value of voxels placed in the viewport.
+const PLACED_VOXEL: u16 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Selected {
+    Entity(Entity),
+    Cell(RhombicCoord),
+}
+
+#[derive(Resource, Default)]
+pub struct Selection {
+    pub selected: Option<Selected>,
+    /// Set when something is picked in the viewport, until the Entities window has scrolled to it.
+    pub reveal: bool,
+}
+
+impl Selection {
+    pub fn entity(&self) -> Option<Entity> {
+        match self.selected? {
+            Selected::Entity(entity) => Some(entity),
+            Selected::Cell(_) => None,
+        }
+    }
+}
+
+/// Selects whatever is under the cursor, the closer of the nearest [`ShowInUIProperties`] entity's
+/// mesh and the nearest voxel. Runs when the viewport is clicked.
+pub(crate) fn pick_selection(
+    mut contexts: EguiContexts,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    entities: Query<
+        (Entity, &Handle<Mesh>, &GlobalTransform, &ComputedVisibility),
+        With<ShowInUIProperties>,
+    >,
+    meshes: Res<Assets<Mesh>>,
+    voxel_world: Res<VoxelWorld>,
+    mut selection: ResMut<Selection>,
+) {
+    let Some(ray) = cursor_ray(&mut contexts, &windows, &camera) else {
+        return;
+    };
+
+    let mut closest: Option<(f32, Selected)> = None;
+    for (entity, mesh, transform, visibility) in &entities {
+        if !visibility.is_visible() {
+            continue;
+        }
+        let Some(mesh) = meshes.get(mesh) else {
+            continue;
+        };
+        if let Some(hit) = ray_mesh_intersection(ray, mesh, transform.compute_matrix()) {
+            if closest.is_none_or(|(distance, _)| hit.distance < distance) {
+                closest = Some((hit.distance, Selected::Entity(entity)));
+            }
+        }
+    }
+
+    let max_distance = closest.map_or(MAX_CELL_PICK_DISTANCE, |(distance, _)| distance);
+    let cell_hit = RhombicRaycast::from_ray(ray)
+        .take_while(|hit| hit.distance < max_distance)
+        .find(|hit| voxel_world.grid().contains(hit.cell));
+    if let Some(hit) = cell_hit {
+        closest = Some((hit.distance, Selected::Cell(hit.cell)));
+    }
+
+    selection.selected = closest.map(|(_, selected)| selected);
+    selection.reveal = matches!(selection.selected, Some(Selected::Entity(_)));
+}
+
+/// Places a voxel against the face of the voxel under the cursor, or on the ground if there
+/// isn't one, and removes the voxel under the cursor while shift is held. Runs when the viewport
+/// is right clicked.
+pub(crate) fn edit_voxels(
+    mut contexts: EguiContexts,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    input: Res<Input<KeyCode>>,
+    mut voxel_world: ResMut<VoxelWorld>,
+) {
+    let Some(ray) = cursor_ray(&mut contexts, &windows, &camera) else {
+        return;
+    };
+    let hit = RhombicRaycast::from_ray(ray)
+        .take_while(|hit| hit.distance < MAX_CELL_PICK_DISTANCE)
+        .find(|hit| voxel_world.grid().contains(hit.cell));
+
+    let edit = if input.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+        hit.map(|hit| voxel_world.remove(hit.cell))
+    } else {
+        let cell = match hit {
+            Some(hit) => hit.entry_face.map(|face| hit.cell.neighbor(face)),
+            // The cell resting on the ground where the ray meets it
+            None => Some(-ray.origin.y / ray.direction.y)
+                .filter(|distance| (0.0..MAX_CELL_PICK_DISTANCE).contains(distance))
+                .map(|distance| RhombicCoord::from_world(ray.get_point(distance) + Vec3::Y * 0.5)),
+        };
+        cell.map(|cell| voxel_world.set(cell, PLACED_VOXEL))
+    };
+    if let Some(Err(error)) = edit {
+        error!("Failed to load chunk: {error}");
+    }
+}
+
+/// The ray under the cursor, unless it is over an egui window.
+fn cursor_ray(
+    contexts: &mut EguiContexts,
+    windows: &Query<&Window, With<PrimaryWindow>>,
+    camera: &Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+) -> Option<Ray> {
+    if contexts.ctx_mut().wants_pointer_input() {
+        return None;
+    }
+    let window = windows.get_single().ok()?;
+    let (camera, camera_transform) = camera.get_single().ok()?;
+    window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
+}
+
+/// The wireframe drawn around the selection.
+#[derive(Resource)]
+pub(crate) struct SelectionHighlight {
+    box_mesh: Handle<Mesh>,
+    cell_mesh: Handle<Mesh>,
+}
+
+#[derive(Component)]
+pub(crate) struct HighlightWireframe;
+
+impl FromWorld for SelectionHighlight {
+    fn from_world(world: &mut World) -> Self {
+        let mut meshes = world.resource_mut::<Assets<Mesh>>();
+        let box_mesh = meshes.add(box_lines());
+        let cell_mesh = meshes.add(cell_lines());
+        let material = world
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(StandardMaterial {
+                base_color: Color::YELLOW,
+                unlit: true,
+                ..default()
+            });
+        world.spawn((
+            PbrBundle {
+                mesh: box_mesh.clone(),
+                material,
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            NotShadowCaster,
+            NotShadowReceiver,
+            // Its `Aabb` is only computed for the box mesh, which is smaller than the cell mesh
+            NoFrustumCulling,
+            HighlightWireframe,
+        ));
+        Self {
+            box_mesh,
+            cell_mesh,
+        }
+    }
+}
+
+/// Moves the highlight over the selected entity's bounding box or the selected cell.
+pub(crate) fn update_selection_highlight(
+    mut selection: ResMut<Selection>,
+    highlight: Res<SelectionHighlight>,
+    selected_entities: Query<
+        (&GlobalTransform, Option<&Handle<Mesh>>),
+        Without<HighlightWireframe>,
+    >,
+    meshes: Res<Assets<Mesh>>,
+    mut wireframes: Query<
+        (&mut Transform, &mut Visibility, &mut Handle<Mesh>),
+        With<HighlightWireframe>,
+    >,
+) {
+    let Ok((mut transform, mut visibility, mut mesh)) = wireframes.get_single_mut() else {
+        return;
+    };
+
+    let placement = match selection.selected {
+        Some(Selected::Entity(entity)) => match selected_entities.get(entity) {
+            Ok((global_transform, mesh)) => {
+                // Not the `Aabb` component, which isn't updated when the mesh asset changes
+                let aabb = mesh
+                    .and_then(|mesh| meshes.get(mesh))
+                    .and_then(|mesh| mesh.compute_aabb());
+                let (center, size) = aabb.map_or((Vec3::ZERO, Vec3::ONE), |aabb| {
+                    (aabb.center.into(), Vec3::from(aabb.half_extents) * 2.0)
+                });
+                let local = Transform::from_translation(center).with_scale(size * 1.02);
+                Some((
+                    Transform::from_matrix(
+                        global_transform.compute_matrix() * local.compute_matrix(),
+                    ),
+                    &highlight.box_mesh,
+                ))
+            }
+            Err(_) => {
+                // The entity was despawned
+                selection.selected = None;
+                None
+            }
+        },
+        Some(Selected::Cell(cell)) => Some((
+            Transform::from_translation(cell.center()).with_scale(Vec3::splat(1.02)),
+            &highlight.cell_mesh,
+        )),
+        None => None,
+    };
+
+    match placement {
+        Some((placement, placement_mesh)) => {
+            *transform = placement;
+            *visibility = Visibility::Visible;
+            if *mesh != *placement_mesh {
+                *mesh = placement_mesh.clone();
+            }
+        }
+        None => *visibility = Visibility::Hidden,
+    }
+}
+
+/// The edges of a unit cube centered on the origin.
+fn box_lines() -> Mesh {
+    let mut positions = vec![];
+    for axis in 0..3 {
+        for a in [-0.5, 0.5] {
+            for b in [-0.5, 0.5] {
+                for end in [-0.5, 0.5] {
+                    let mut position = [0.0; 3];
+                    position[axis] = end;
+                    position[(axis + 1) % 3] = a;
+                    position[(axis + 2) % 3] = b;
+                    positions.push(position);
+                }
+            }
+        }
+    }
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh
+}
+
+/// The edges of the cell from `rhombic_dodecahedron()`.
+fn cell_lines() -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        EDGE_VERTICES
+            .into_iter()
+            .flatten()
+            .map(|vertex| CELL_VERTICES[vertex].to_array())
+            .collect::<Vec<_>>(),
+    );
+    mesh
+}
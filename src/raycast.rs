@@ -0,0 +1,348 @@
+use bevy::{
+    prelude::*,
+    render::{mesh::VertexAttributeValues, render_resource::PrimitiveTopology},
+};
+
+use crate::{RhombicCoord, NEIGHBOR_OFFSETS};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RhombicRaycastHit {
+    pub cell: RhombicCoord,
+    /// Index into [`NEIGHBOR_OFFSETS`] of the face the ray entered `cell` through, or `None` for
+    /// the cell containing the ray origin.
+    pub entry_face: Option<usize>,
+    /// Distance along the ray to `point`, in world units.
+    pub distance: f32,
+    pub point: Vec3,
+}
+
+/// Walks every cell a ray passes through, in order, starting with the cell containing its origin.
+///
+/// The walk never ends on its own, so limit it with `take_while` on the hit distance. When the ray
+/// leaves a cell exactly through an edge or vertex, it steps through the face with the lowest
+/// index, which can produce a zero-length hit in a cell the ray only grazes.
+#[derive(Clone, Debug)]
+pub struct RhombicRaycast {
+    origin: Vec3,
+    direction: Vec3,
+    next: Option<RhombicRaycastHit>,
+}
+
+impl RhombicRaycast {
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize_or_zero(),
+            next: Some(RhombicRaycastHit {
+                cell: RhombicCoord::from_world(origin),
+                entry_face: None,
+                distance: 0.0,
+                point: origin,
+            }),
+        }
+    }
+
+    pub fn from_ray(ray: Ray) -> Self {
+        Self::new(ray.origin, ray.direction)
+    }
+}
+
+impl Iterator for RhombicRaycast {
+    type Item = RhombicRaycastHit;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let hit = self.next.take()?;
+
+        // Each face `i` lies on the plane `offset_i . (p - center) = 1`
+        let relative_origin = self.origin - hit.cell.center();
+        let mut exit: Option<(usize, f32)> = None;
+        for (face, offset) in NEIGHBOR_OFFSETS.into_iter().enumerate() {
+            let offset = offset.center();
+            let speed = offset.dot(self.direction);
+            if speed <= 0.0 {
+                continue;
+            }
+            let distance = (1.0 - offset.dot(relative_origin)) / speed;
+            if exit.is_none_or(|(_, exit_distance)| distance < exit_distance) {
+                exit = Some((face, distance));
+            }
+        }
+
+        self.next = exit.map(|(face, distance)| {
+            let distance = distance.max(hit.distance);
+            RhombicRaycastHit {
+                cell: hit.cell.neighbor(face),
+                entry_face: Some((face + 6) % 12),
+                distance,
+                point: self.origin + self.direction * distance,
+            }
+        });
+        Some(hit)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeshRaycastHit {
+    /// Distance along the ray to `point`, in world units.
+    pub distance: f32,
+    pub point: Vec3,
+    /// The normal of the hit triangle, facing back towards the ray.
+    pub normal: Vec3,
+    /// Index of the hit triangle in the order the mesh's topology lists them.
+    pub triangle: usize,
+}
+
+/// Finds the closest point where a ray hits a triangle list or strip mesh, placed in the world by
+/// `mesh_to_world`. Triangles are hit from both sides.
+pub fn ray_mesh_intersection(ray: Ray, mesh: &Mesh, mesh_to_world: Mat4) -> Option<MeshRaycastHit> {
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+        mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+        return None;
+    };
+    let indices = match mesh.indices() {
+        Some(indices) => indices.iter().collect::<Vec<_>>(),
+        None => (0..positions.len()).collect(),
+    };
+    let triangles = match mesh.primitive_topology() {
+        PrimitiveTopology::TriangleList => indices
+            .chunks_exact(3)
+            .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+            .collect::<Vec<_>>(),
+        // Every other triangle of a strip is wound the other way
+        PrimitiveTopology::TriangleStrip => indices
+            .windows(3)
+            .enumerate()
+            .map(|(i, triangle)| {
+                if i % 2 == 0 {
+                    [triangle[0], triangle[1], triangle[2]]
+                } else {
+                    [triangle[1], triangle[0], triangle[2]]
+                }
+            })
+            .collect(),
+        _ => return None,
+    };
+
+    let direction = ray.direction.normalize_or_zero();
+    let mut closest: Option<MeshRaycastHit> = None;
+    for (index, triangle) in triangles.into_iter().enumerate() {
+        let Some(corners) = triangle
+            .iter()
+            .map(|&i| Some(mesh_to_world.transform_point3(Vec3::from(*positions.get(i)?))))
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+        let Some(distance) =
+            ray_triangle_intersection(ray.origin, direction, [corners[0], corners[1], corners[2]])
+        else {
+            continue;
+        };
+        if closest.is_none_or(|closest| distance < closest.distance) {
+            let normal = (corners[1] - corners[0])
+                .cross(corners[2] - corners[0])
+                .normalize_or_zero();
+            closest = Some(MeshRaycastHit {
+                distance,
+                point: ray.origin + direction * distance,
+                normal: if normal.dot(direction) > 0.0 {
+                    -normal
+                } else {
+                    normal
+                },
+                triangle: index,
+            });
+        }
+    }
+    closest
+}
+
+/// Möller–Trumbore, returning the distance along the normalized `direction`.
+fn ray_triangle_intersection(origin: Vec3, direction: Vec3, [a, b, c]: [Vec3; 3]) -> Option<f32> {
+    let ab = b - a;
+    let ac = c - a;
+    let p = direction.cross(ac);
+    let determinant = ab.dot(p);
+    // The ray is parallel to the triangle, or the triangle is degenerate
+    if determinant.abs() <= f32::EPSILON * ab.length() * ac.length() {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+    let to_origin = origin - a;
+    let u = to_origin.dot(p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = to_origin.cross(ab);
+    let v = direction.dot(q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let distance = ac.dot(q) * inverse_determinant;
+    (distance >= 0.0).then_some(distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rhombic_dodecahedron;
+
+    /// Random rays starting within a few cells of the origin.
+    fn random_rays(count: usize) -> Vec<Ray> {
+        let mut state = 0x9e37_79b9_u32;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as f32 / u32::MAX as f32 * 2.0 - 1.0
+        };
+        (0..count)
+            .map(|_| Ray {
+                origin: Vec3::new(random(), random(), random()) * 5.0,
+                direction: Vec3::new(random(), random(), random()).normalize(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn raycast_matches_dense_sampling() {
+        const LENGTH: f32 = 20.0;
+        const STEP: f32 = 0.002;
+        for ray in random_rays(200) {
+            let hits = RhombicRaycast::from_ray(ray)
+                .take_while(|hit| hit.distance < LENGTH)
+                .collect::<Vec<_>>();
+            for pair in hits.windows(2) {
+                assert!(pair[0].distance <= pair[1].distance);
+                assert_eq!(
+                    pair[0]
+                        .cell
+                        .neighbor((pair[1].entry_face.unwrap() + 6) % 12),
+                    pair[1].cell
+                );
+            }
+
+            let mut hit = 0;
+            for i in 0..(LENGTH / STEP) as usize {
+                let distance = i as f32 * STEP;
+                while hit + 1 < hits.len() && hits[hit + 1].distance <= distance {
+                    hit += 1;
+                }
+                // Too close to a boundary for rounding to be trusted
+                let near_boundary = hits[hit].distance > distance - 1e-4
+                    || hits
+                        .get(hit + 1)
+                        .is_some_and(|next| next.distance < distance + 1e-4);
+                if near_boundary {
+                    continue;
+                }
+                let point = ray.origin + ray.direction * distance;
+                assert_eq!(
+                    RhombicCoord::from_world(point),
+                    hits[hit].cell,
+                    "{ray:?} at {distance}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn raycast_entry_points_match_the_cell_mesh() {
+        let mesh = rhombic_dodecahedron();
+        for ray in random_rays(200) {
+            for hit in RhombicRaycast::from_ray(ray)
+                .skip(1)
+                .take_while(|hit| hit.distance < 20.0)
+            {
+                let mesh_hit =
+                    ray_mesh_intersection(ray, &mesh, Mat4::from_translation(hit.cell.center()))
+                        .unwrap();
+                assert!(
+                    (mesh_hit.distance - hit.distance).abs() < 1e-3,
+                    "{ray:?} entered {:?} at {} but hit its mesh at {}",
+                    hit.cell,
+                    hit.distance,
+                    mesh_hit.distance
+                );
+                assert!((mesh_hit.point - hit.point).length() < 1e-3);
+                let entry_normal = NEIGHBOR_OFFSETS[hit.entry_face.unwrap()]
+                    .center()
+                    .normalize();
+                assert!(mesh_hit.normal.dot(entry_normal) > 0.999);
+            }
+        }
+    }
+
+    const S: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+    #[test]
+    fn ray_hits_the_near_side_of_a_moved_cell() {
+        let mesh = rhombic_dodecahedron();
+        let mesh_to_world = Mat4::from_translation(Vec3::new(3.0, 0.0, 0.0));
+        // Not normalized, which shouldn't change the distance
+        let ray = Ray {
+            origin: Vec3::new(3.1, 0.2, -5.0),
+            direction: Vec3::new(0.0, 0.0, 2.0),
+        };
+        let hit = ray_mesh_intersection(ray, &mesh, mesh_to_world).unwrap();
+        // Through the face facing (0, 1, -1), where y - z = 1
+        assert!((hit.distance - 4.2).abs() < 1e-5);
+        assert!((hit.point - Vec3::new(3.1, 0.2, -0.8)).length() < 1e-5);
+        assert!((hit.normal - Vec3::new(0.0, S, -S)).length() < 1e-5);
+        // Every face is two triangles, in face order
+        assert_eq!(hit.triangle / 2, 5);
+    }
+
+    #[test]
+    fn ray_misses_beside_and_behind_a_cell() {
+        let mesh = rhombic_dodecahedron();
+        let mesh_to_world = Mat4::from_translation(Vec3::new(3.0, 0.0, 0.0));
+        let beside = Ray {
+            origin: Vec3::new(4.5, 0.0, -5.0),
+            direction: Vec3::Z,
+        };
+        assert!(ray_mesh_intersection(beside, &mesh, mesh_to_world).is_none());
+        let away = Ray {
+            origin: Vec3::new(3.1, 0.2, -5.0),
+            direction: Vec3::NEG_Z,
+        };
+        assert!(ray_mesh_intersection(away, &mesh, mesh_to_world).is_none());
+    }
+
+    #[test]
+    fn ray_from_inside_hits_the_back_of_a_face() {
+        let mesh = rhombic_dodecahedron();
+        let ray = Ray {
+            origin: Vec3::new(0.0, 0.2, 0.1),
+            direction: Vec3::Z,
+        };
+        let hit = ray_mesh_intersection(ray, &mesh, Mat4::IDENTITY).unwrap();
+        // Out through the face facing (0, 1, 1), with the normal turned back towards the ray
+        assert!((hit.distance - 0.7).abs() < 1e-5);
+        assert!((hit.normal - Vec3::new(0.0, -S, -S)).length() < 1e-5);
+        assert_eq!(hit.triangle / 2, 4);
+    }
+
+    #[test]
+    fn grazing_rays_only_hit_inside_the_cell() {
+        let mesh = rhombic_dodecahedron();
+        // Along the face facing (0, 1, -1), through its 3-valent corners at x = -0.5 and 0.5
+        let grazing = |offset: f32| Ray {
+            origin: Vec3::new(-5.0, 0.5 - offset, -0.5),
+            direction: Vec3::X,
+        };
+        assert!(ray_mesh_intersection(grazing(-1e-3), &mesh, Mat4::IDENTITY).is_none());
+
+        let inside = ray_mesh_intersection(grazing(1e-3), &mesh, Mat4::IDENTITY).unwrap();
+        // In through the face facing (-1, 0, -1), since the face it's along is never crossed
+        assert!((inside.distance - 4.5).abs() < 1e-5);
+        assert!((inside.normal - Vec3::new(-S, 0.0, -S)).length() < 1e-5);
+
+        // Touching the corner exactly still counts, without the parallel face giving a bad hit
+        let touching = ray_mesh_intersection(grazing(0.0), &mesh, Mat4::IDENTITY).unwrap();
+        assert!((touching.distance - 4.5).abs() < 1e-5);
+        assert!(touching.normal.is_finite());
+        assert!(touching.normal.dot(Vec3::X) < 0.0);
+    }
+}
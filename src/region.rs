@@ -0,0 +1,420 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use bevy::{prelude::*, utils::HashMap};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+
+use crate::{
+    cell_at_index, cell_index, chunk_of, RhombicCoord, RhombicGrid, CHUNK_CELL_COUNT, CHUNK_SIZE,
+};
+
+pub const REGION_VERSION: u32 = 1;
+
+/// The number of chunks along each axis of a region file.
+pub const REGION_SIZE: i32 = 8;
+const REGION_CHUNK_COUNT: usize = (REGION_SIZE * REGION_SIZE * REGION_SIZE) as usize;
+
+const MAGIC: &[u8; 4] = b"RDRG";
+const HEADER_SIZE: usize = 4 + 4 + 4 + 4 + 4;
+const TABLE_SIZE: usize = REGION_CHUNK_COUNT * 8;
+
+/// The cells stored in one chunk of a region file.
+pub type ChunkCells = Vec<(RhombicCoord, u16)>;
+
+/// The lattice a region file was written for. Files for a different lattice can't be read.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RegionHeader {
+    pub version: u32,
+    pub chunk_size: u32,
+    pub region_size: u32,
+    /// The world space distance between the centers of cells two steps apart along an axis,
+    /// halved. `1.0` for the mesh from `rhombic_dodecahedron()`.
+    pub cell_size: f32,
+}
+
+impl RegionHeader {
+    pub const CURRENT: Self = Self {
+        version: REGION_VERSION,
+        chunk_size: CHUNK_SIZE as u32,
+        region_size: REGION_SIZE as u32,
+        cell_size: 1.0,
+    };
+
+    fn read(reader: &mut impl Read) -> io::Result<Self> {
+        let mut bytes = [0; HEADER_SIZE];
+        reader.read_exact(&mut bytes)?;
+        if &bytes[0..4] != MAGIC {
+            return Err(invalid_data("not a region file"));
+        }
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        Ok(Self {
+            version: u32_at(4),
+            chunk_size: u32_at(8),
+            region_size: u32_at(12),
+            cell_size: f32::from_bits(u32_at(16)),
+        })
+    }
+
+    fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&self.version.to_le_bytes())?;
+        writer.write_all(&self.chunk_size.to_le_bytes())?;
+        writer.write_all(&self.region_size.to_le_bytes())?;
+        writer.write_all(&self.cell_size.to_le_bytes())
+    }
+}
+
+/// Brings a region file written by an older version up to date, given its header and the
+/// decompressed data of one of its chunks.
+///
+/// Every format change should bump [`REGION_VERSION`] and add a step here from the previous
+/// version, so old worlds keep loading.
+fn migrate_chunk(header: &RegionHeader, data: Vec<u8>) -> io::Result<Vec<u8>> {
+    if header.chunk_size != CHUNK_SIZE as u32
+        || header.region_size != REGION_SIZE as u32
+        || header.cell_size != 1.0
+    {
+        return Err(invalid_data("region file is for a different lattice"));
+    }
+    match header.version {
+        REGION_VERSION => Ok(data),
+        version => Err(invalid_data(&format!(
+            "unsupported region file version {version}"
+        ))),
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub fn region_of(chunk: IVec3) -> IVec3 {
+    IVec3::new(
+        chunk.x.div_euclid(REGION_SIZE),
+        chunk.y.div_euclid(REGION_SIZE),
+        chunk.z.div_euclid(REGION_SIZE),
+    )
+}
+
+fn chunk_slot(chunk: IVec3) -> usize {
+    let local = chunk - region_of(chunk) * REGION_SIZE;
+    (local.x + local.y * REGION_SIZE + local.z * REGION_SIZE * REGION_SIZE) as usize
+}
+
+fn slot_chunk(region: IVec3, slot: usize) -> IVec3 {
+    let slot = slot as i32;
+    region * REGION_SIZE
+        + IVec3::new(
+            slot % REGION_SIZE,
+            slot / REGION_SIZE % REGION_SIZE,
+            slot / (REGION_SIZE * REGION_SIZE),
+        )
+}
+
+/// A directory of region files, each holding the chunks of a `REGION_SIZE^3` block of chunks.
+///
+/// A region file is a header, a table of the offset and length of every chunk in the region,
+/// then the chunks themselves, each compressed on its own. A chunk is a bitmask of which of its
+/// cells are occupied followed by the value of every occupied cell.
+#[derive(Clone, Debug)]
+pub struct RegionStore {
+    directory: PathBuf,
+}
+
+impl RegionStore {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn region_path(&self, region: IVec3) -> PathBuf {
+        self.directory
+            .join(format!("r.{}.{}.{}.rdr", region.x, region.y, region.z))
+    }
+
+    /// Returns `None` if the chunk has never been saved.
+    pub fn load_chunk(&self, chunk: IVec3) -> io::Result<Option<ChunkCells>> {
+        let mut file = match File::open(self.region_path(region_of(chunk))) {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error),
+        };
+        let header = RegionHeader::read(&mut file)?;
+        file.seek(SeekFrom::Start(
+            (HEADER_SIZE + chunk_slot(chunk) * 8) as u64,
+        ))?;
+        let mut entry = [0; 8];
+        file.read_exact(&mut entry)?;
+        let (offset, length) = table_entry(&entry);
+        if length == 0 {
+            return Ok(None);
+        }
+        file.seek(SeekFrom::Start(offset as u64))?;
+        let mut compressed = vec![0; length as usize];
+        file.read_exact(&mut compressed)?;
+        decode_chunk(chunk, &migrate_chunk(&header, decompress(&compressed)?)?).map(Some)
+    }
+
+    /// Loads every chunk saved in a region.
+    pub fn load_region(&self, region: IVec3) -> io::Result<HashMap<IVec3, ChunkCells>> {
+        self.read_region(region)?
+            .into_iter()
+            .map(|(chunk, compressed)| Ok((chunk, decode_chunk(chunk, &decompress(&compressed)?)?)))
+            .collect()
+    }
+
+    /// Writes the current contents of `chunks` from `grid`, rewriting each region file they
+    /// are in once. Empty chunks are removed from their region.
+    ///
+    /// The other chunks in those regions are copied over still compressed, so saving a chunk
+    /// doesn't decode the rest of its region.
+    pub fn save_chunks(
+        &self,
+        grid: &RhombicGrid<u16>,
+        chunks: impl IntoIterator<Item = IVec3>,
+    ) -> io::Result<()> {
+        let mut regions: HashMap<IVec3, Vec<IVec3>> = HashMap::default();
+        for chunk in chunks {
+            regions.entry(region_of(chunk)).or_default().push(chunk);
+        }
+
+        std::fs::create_dir_all(&self.directory)?;
+        for (region, chunks) in regions {
+            let mut region_chunks = self.read_region(region)?;
+            for chunk in chunks {
+                let cells = grid
+                    .iter_chunk(chunk)
+                    .map(|(cell, &value)| (cell, value))
+                    .collect::<Vec<_>>();
+                if cells.is_empty() {
+                    region_chunks.remove(&chunk);
+                } else {
+                    region_chunks.insert(chunk, compress(&encode_chunk(&cells))?);
+                }
+            }
+            self.write_region(region, &region_chunks)?;
+        }
+        Ok(())
+    }
+
+    /// The compressed data of every chunk saved in a region, migrated to the current version.
+    fn read_region(&self, region: IVec3) -> io::Result<HashMap<IVec3, Vec<u8>>> {
+        let bytes = match std::fs::read(self.region_path(region)) {
+            Ok(bytes) => bytes,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(HashMap::default()),
+            Err(error) => return Err(error),
+        };
+        let header = RegionHeader::read(&mut bytes.as_slice())?;
+        let table = bytes
+            .get(HEADER_SIZE..HEADER_SIZE + TABLE_SIZE)
+            .ok_or_else(|| invalid_data("region file is truncated"))?;
+
+        let mut chunks = HashMap::default();
+        for (slot, entry) in table.chunks_exact(8).enumerate() {
+            let (offset, length) = table_entry(entry);
+            if length == 0 {
+                continue;
+            }
+            let compressed = bytes
+                .get(offset as usize..offset as usize + length as usize)
+                .ok_or_else(|| invalid_data("region file is truncated"))?;
+            let compressed = if header == RegionHeader::CURRENT {
+                compressed.to_vec()
+            } else {
+                compress(&migrate_chunk(&header, decompress(compressed)?)?)?
+            };
+            chunks.insert(slot_chunk(region, slot), compressed);
+        }
+        Ok(chunks)
+    }
+
+    fn write_region(&self, region: IVec3, chunks: &HashMap<IVec3, Vec<u8>>) -> io::Result<()> {
+        let path = self.region_path(region);
+        if chunks.is_empty() {
+            return match std::fs::remove_file(path) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+                _ => Ok(()),
+            };
+        }
+
+        let mut table = vec![0; TABLE_SIZE];
+        let mut data = vec![];
+        for (&chunk, compressed) in chunks {
+            let offset = (HEADER_SIZE + TABLE_SIZE + data.len()) as u32;
+            let slot = chunk_slot(chunk) * 8;
+            table[slot..slot + 4].copy_from_slice(&offset.to_le_bytes());
+            table[slot + 4..slot + 8].copy_from_slice(&(compressed.len() as u32).to_le_bytes());
+            data.extend_from_slice(compressed);
+        }
+
+        // Write next to the old file and swap it in, so a crash can't leave half a region
+        let temporary_path = path.with_extension("rdr.tmp");
+        let mut writer = BufWriter::new(File::create(&temporary_path)?);
+        RegionHeader::CURRENT.write(&mut writer)?;
+        writer.write_all(&table)?;
+        writer.write_all(&data)?;
+        writer
+            .into_inner()
+            .map_err(|error| error.into_error())?
+            .sync_all()?;
+        std::fs::rename(temporary_path, path)
+    }
+}
+
+fn table_entry(entry: &[u8]) -> (u32, u32) {
+    (
+        u32::from_le_bytes(entry[0..4].try_into().unwrap()),
+        u32::from_le_bytes(entry[4..8].try_into().unwrap()),
+    )
+}
+
+fn encode_chunk(cells: &[(RhombicCoord, u16)]) -> Vec<u8> {
+    let mut values = vec![None; CHUNK_CELL_COUNT];
+    for &(cell, value) in cells {
+        values[cell_index(cell)] = Some(value);
+    }
+
+    let mut data = vec![0; CHUNK_CELL_COUNT / 8];
+    for (index, value) in values.iter().enumerate() {
+        if value.is_some() {
+            data[index / 8] |= 1 << (index % 8);
+        }
+    }
+    data.extend(values.into_iter().flatten().flat_map(u16::to_le_bytes));
+    data
+}
+
+fn decode_chunk(chunk: IVec3, data: &[u8]) -> io::Result<ChunkCells> {
+    let (occupancy, values) = data
+        .split_at_checked(CHUNK_CELL_COUNT / 8)
+        .ok_or_else(|| invalid_data("chunk is truncated"))?;
+    let occupied_count = occupancy
+        .iter()
+        .map(|byte| byte.count_ones() as usize)
+        .sum::<usize>();
+    if values.len() != occupied_count * 2 {
+        return Err(invalid_data("chunk has the wrong number of values"));
+    }
+    let occupied =
+        (0..CHUNK_CELL_COUNT).filter(|index| occupancy[index / 8] & (1 << (index % 8)) != 0);
+    let cells = occupied
+        .zip(values.chunks_exact(2))
+        .map(|(index, value)| {
+            let cell = cell_at_index(chunk, index);
+            debug_assert_eq!(chunk_of(cell), chunk);
+            (cell, u16::from_le_bytes([value[0], value[1]]))
+        })
+        .collect::<Vec<_>>();
+    Ok(cells)
+}
+
+fn compress(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut decompressed = vec![];
+    ZlibDecoder::new(data).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk_cells(chunk: IVec3, step: usize) -> ChunkCells {
+        (0..CHUNK_CELL_COUNT)
+            .step_by(step)
+            .map(|index| (cell_at_index(chunk, index), (index * 7) as u16))
+            .collect()
+    }
+
+    #[test]
+    fn chunks_round_trip() {
+        for chunk in [IVec3::ZERO, IVec3::new(-1, 2, -3)] {
+            for step in [1, 3, 100, CHUNK_CELL_COUNT] {
+                let cells = chunk_cells(chunk, step);
+                assert_eq!(decode_chunk(chunk, &encode_chunk(&cells)).unwrap(), cells);
+            }
+        }
+        let empty = encode_chunk(&[]);
+        assert_eq!(empty.len(), CHUNK_CELL_COUNT / 8);
+        assert!(decode_chunk(IVec3::ZERO, &empty).unwrap().is_empty());
+    }
+
+    #[test]
+    fn corrupt_chunks_are_rejected() {
+        let cells = chunk_cells(IVec3::ZERO, 5);
+        let data = encode_chunk(&cells);
+        let is_invalid = |data: &[u8]| {
+            decode_chunk(IVec3::ZERO, data).unwrap_err().kind() == io::ErrorKind::InvalidData
+        };
+
+        // Cut off in the bitmask, or in the values
+        assert!(is_invalid(&data[..CHUNK_CELL_COUNT / 8 - 1]));
+        assert!(is_invalid(&data[..data.len() - 2]));
+        assert!(is_invalid(&data[..data.len() - 1]));
+        // Values left over
+        let mut extra = data.clone();
+        extra.extend([1, 2]);
+        assert!(is_invalid(&extra));
+        // A cell marked occupied without a value, or a value for a cell that isn't
+        let mut extra_cell = data.clone();
+        extra_cell[0] |= 0b10;
+        assert!(is_invalid(&extra_cell));
+        let mut missing_cell = data;
+        missing_cell[0] &= !0b1;
+        assert!(is_invalid(&missing_cell));
+    }
+
+    #[test]
+    fn regions_round_trip() {
+        let directory =
+            std::env::temp_dir().join(format!("grid-region-test-{}", std::process::id()));
+        let store = RegionStore::new(&directory);
+        // Chunks on both sides of a region border, and in a negative region
+        let chunks = [
+            IVec3::new(REGION_SIZE - 1, 0, 0),
+            IVec3::new(REGION_SIZE, 0, 0),
+            IVec3::new(-1, -1, -REGION_SIZE - 1),
+        ];
+        let mut grid = RhombicGrid::new();
+        for (i, &chunk) in chunks.iter().enumerate() {
+            for (cell, value) in chunk_cells(chunk, i + 2) {
+                grid.set(cell, value);
+            }
+        }
+        store.save_chunks(&grid, chunks).unwrap();
+
+        for chunk in chunks {
+            let mut expected = grid
+                .iter_chunk(chunk)
+                .map(|(cell, &value)| (cell, value))
+                .collect::<Vec<_>>();
+            expected.sort_by_key(|&(cell, _)| cell_index(cell));
+            assert_eq!(store.load_chunk(chunk).unwrap(), Some(expected));
+        }
+        assert_eq!(store.load_region(region_of(chunks[0])).unwrap().len(), 1);
+        assert_eq!(store.load_chunk(IVec3::new(1, 0, 0)).unwrap(), None);
+
+        // Emptied chunks are removed, along with regions left without any
+        grid.remove_chunk(chunks[1]);
+        store.save_chunks(&grid, [chunks[1]]).unwrap();
+        assert_eq!(store.load_chunk(chunks[1]).unwrap(), None);
+        assert!(!store.region_path(region_of(chunks[1])).exists());
+        assert!(store.load_chunk(chunks[0]).unwrap().is_some());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,483 @@
+use bevy::prelude::*;
+
+use crate::CellSymmetry;
+
+/// A cell of the rhombic dodecahedral grid.
+///
+/// Cells are the points of the face-centered cubic lattice: integer coordinates whose sum is
+/// even. Coordinates are in the same units as the mesh from `rhombic_dodecahedron()`, so a cell's
+/// world space center is just its coordinates as a [`Vec3`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RhombicCoord {
+    x: i32,
+    y: i32,
+    z: i32,
+}
+
+/// Offsets to the 12 cells sharing a face, ordered so that the opposite of offset `i` is
+/// offset `(i + 6) % 12`.
+pub const NEIGHBOR_OFFSETS: [RhombicCoord; 12] = [
+    RhombicCoord::new_unchecked(1, 1, 0),
+    RhombicCoord::new_unchecked(1, -1, 0),
+    RhombicCoord::new_unchecked(1, 0, 1),
+    RhombicCoord::new_unchecked(1, 0, -1),
+    RhombicCoord::new_unchecked(0, 1, 1),
+    RhombicCoord::new_unchecked(0, 1, -1),
+    RhombicCoord::new_unchecked(-1, -1, 0),
+    RhombicCoord::new_unchecked(-1, 1, 0),
+    RhombicCoord::new_unchecked(-1, 0, -1),
+    RhombicCoord::new_unchecked(-1, 0, 1),
+    RhombicCoord::new_unchecked(0, -1, -1),
+    RhombicCoord::new_unchecked(0, -1, 1),
+];
+
+/// Offsets to the 6 cells that only share a 4-valent vertex, ordered so that the opposite of
+/// offset `i` is offset `(i + 3) % 6`.
+pub const SECOND_SHELL_OFFSETS: [RhombicCoord; 6] = [
+    RhombicCoord::new_unchecked(2, 0, 0),
+    RhombicCoord::new_unchecked(0, 2, 0),
+    RhombicCoord::new_unchecked(0, 0, 2),
+    RhombicCoord::new_unchecked(-2, 0, 0),
+    RhombicCoord::new_unchecked(0, -2, 0),
+    RhombicCoord::new_unchecked(0, 0, -2),
+];
+
+impl RhombicCoord {
+    pub const ZERO: Self = Self::new_unchecked(0, 0, 0);
+
+    /// Panics if `x + y + z` is odd.
+    pub fn new(x: i32, y: i32, z: i32) -> Self {
+        Self::try_new(x, y, z).expect("rhombic coordinates must have an even sum")
+    }
+
+    pub fn try_new(x: i32, y: i32, z: i32) -> Option<Self> {
+        Self::is_valid(x, y, z).then_some(Self { x, y, z })
+    }
+
+    const fn new_unchecked(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    pub fn is_valid(x: i32, y: i32, z: i32) -> bool {
+        (x.wrapping_add(y).wrapping_add(z)) & 1 == 0
+    }
+
+    pub fn x(self) -> i32 {
+        self.x
+    }
+
+    pub fn y(self) -> i32 {
+        self.y
+    }
+
+    pub fn z(self) -> i32 {
+        self.z
+    }
+
+    pub fn as_ivec3(self) -> IVec3 {
+        IVec3::new(self.x, self.y, self.z)
+    }
+
+    pub fn from_ivec3(v: IVec3) -> Option<Self> {
+        Self::try_new(v.x, v.y, v.z)
+    }
+
+    pub fn neighbor(self, index: usize) -> Self {
+        self + NEIGHBOR_OFFSETS[index]
+    }
+
+    pub fn neighbors(self) -> [Self; 12] {
+        NEIGHBOR_OFFSETS.map(|offset| self + offset)
+    }
+
+    pub fn second_shell(self) -> [Self; 6] {
+        SECOND_SHELL_OFFSETS.map(|offset| self + offset)
+    }
+
+    /// The minimum number of face steps between `self` and `other`.
+    pub fn distance(self, other: Self) -> u32 {
+        let delta = (other - self).as_ivec3().abs();
+        let max = delta.max_element();
+        let half_sum = (delta.x + delta.y + delta.z) / 2;
+        max.max(half_sum) as u32
+    }
+
+    /// All cells at most `radius` face steps away, including `self`.
+    pub fn range(self, radius: u32) -> impl Iterator<Item = Self> {
+        self.cells_in_bounds(radius)
+            .filter(move |&coord| self.distance(coord) <= radius)
+    }
+
+    /// All cells exactly `radius` face steps away.
+    pub fn ring(self, radius: u32) -> impl Iterator<Item = Self> {
+        self.cells_in_bounds(radius)
+            .filter(move |&coord| self.distance(coord) == radius)
+    }
+
+    /// The face-adjacent cells approximating the segment between the centers of `self` and
+    /// `other`, including both ends.
+    ///
+    /// Each step moves to a neighbor one step closer to the far end whose center is closest to
+    /// the line through both centers, preferring the lowest index in [`NEIGHBOR_OFFSETS`] on
+    /// ties. The walk always starts from the lesser of the two cells, so `a.line(b)` is
+    /// `b.line(a)` reversed.
+    pub fn line(self, other: Self) -> Vec<Self> {
+        if other < self {
+            let mut line = other.line(self);
+            line.reverse();
+            return line;
+        }
+
+        let direction = other - self;
+        let mut line = vec![self];
+        let mut current = self;
+        while current != other {
+            let remaining = current.distance(other);
+            current = current
+                .neighbors()
+                .into_iter()
+                .filter(|&neighbor| neighbor.distance(other) < remaining)
+                .min_by_key(|&neighbor| {
+                    // Squared distance to the line, scaled by the squared length of `direction`
+                    let offset = neighbor - self;
+                    let [x, y, z] = [
+                        offset.y as i64 * direction.z as i64 - offset.z as i64 * direction.y as i64,
+                        offset.z as i64 * direction.x as i64 - offset.x as i64 * direction.z as i64,
+                        offset.x as i64 * direction.y as i64 - offset.y as i64 * direction.x as i64,
+                    ];
+                    x * x + y * y + z * z
+                })
+                .unwrap();
+            line.push(current);
+        }
+        line
+    }
+
+    fn cells_in_bounds(self, radius: u32) -> impl Iterator<Item = Self> {
+        // A cell n steps away never differs by more than n along any axis
+        let r = radius as i32;
+        (-r..=r).flat_map(move |x| {
+            (-r..=r).flat_map(move |y| {
+                let z_start = -r + (x + y - r).rem_euclid(2);
+                (z_start..=r)
+                    .step_by(2)
+                    .map(move |z| self + Self::new_unchecked(x, y, z))
+            })
+        })
+    }
+
+    pub fn center(self) -> Vec3 {
+        self.as_ivec3().as_vec3()
+    }
+
+    /// Returns the cell containing `position`, i.e. the closest lattice point.
+    ///
+    /// Points on a shared face, edge or vertex are resolved by rounding halves towards positive
+    /// infinity and, when the rounded point has to be moved back onto the lattice, moving the
+    /// axis with the largest rounding error (preferring x, then y, then z) towards `position`,
+    /// or towards positive infinity if it is exactly halfway.
+    pub fn from_world(position: Vec3) -> Self {
+        let rounded = (position + 0.5).floor();
+        let mut coord = rounded.as_ivec3();
+        if !Self::is_valid(coord.x, coord.y, coord.z) {
+            let error = position - rounded;
+            let axis = if error.x.abs() >= error.y.abs() && error.x.abs() >= error.z.abs() {
+                0
+            } else if error.y.abs() >= error.z.abs() {
+                1
+            } else {
+                2
+            };
+            coord[axis] += if error[axis] >= 0.0 { 1 } else { -1 };
+        }
+        Self::new_unchecked(coord.x, coord.y, coord.z)
+    }
+
+    /// Returns `None` if `center` is not (approximately) the center of a cell.
+    pub fn from_center(center: Vec3) -> Option<Self> {
+        let rounded = center.round();
+        if !rounded.abs_diff_eq(center, 1e-4) {
+            return None;
+        }
+        Self::from_ivec3(rounded.as_ivec3())
+    }
+}
+
+/// The rotation closest to `rotation` that maps cells onto themselves.
+pub fn snap_rotation_to_grid(rotation: Quat) -> Quat {
+    CellSymmetry::nearest_rotation(rotation).rotation()
+}
+
+/// Moves a transform to the nearest cell center and turns it to the nearest of the rotations that
+/// map cells onto themselves. Its scale is left alone, so a negative scale can still mirror it.
+pub fn snap_transform_to_grid(transform: Transform) -> Transform {
+    Transform {
+        translation: RhombicCoord::from_world(transform.translation).center(),
+        rotation: snap_rotation_to_grid(transform.rotation),
+        scale: transform.scale,
+    }
+}
+
+impl std::ops::Add for RhombicCoord {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new_unchecked(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+    }
+}
+
+impl std::ops::AddAssign for RhombicCoord {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl std::ops::Sub for RhombicCoord {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new_unchecked(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+impl std::ops::SubAssign for RhombicCoord {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl std::ops::Neg for RhombicCoord {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new_unchecked(-self.x, -self.y, -self.z)
+    }
+}
+
+impl std::ops::Mul<i32> for RhombicCoord {
+    type Output = Self;
+
+    fn mul(self, rhs: i32) -> Self {
+        Self::new_unchecked(self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
+impl From<RhombicCoord> for IVec3 {
+    fn from(coord: RhombicCoord) -> Self {
+        coord.as_ivec3()
+    }
+}
+
+impl From<RhombicCoord> for Vec3 {
+    fn from(coord: RhombicCoord) -> Self {
+        coord.center()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The squared distance from `position` to the closest lattice point, by brute force.
+    fn closest_distance_squared(position: Vec3) -> f32 {
+        let base = position.floor().as_ivec3();
+        (-2..=2)
+            .flat_map(|x| (-2..=2).flat_map(move |y| (-2..=2).map(move |z| IVec3::new(x, y, z))))
+            .filter_map(|offset| RhombicCoord::from_ivec3(base + offset))
+            .map(|coord| coord.center().distance_squared(position))
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    #[test]
+    fn offsets_are_cells_paired_with_their_opposites() {
+        for (i, &offset) in NEIGHBOR_OFFSETS.iter().enumerate() {
+            assert!(RhombicCoord::is_valid(offset.x, offset.y, offset.z));
+            assert_eq!(offset + NEIGHBOR_OFFSETS[(i + 6) % 12], RhombicCoord::ZERO);
+            assert_eq!(RhombicCoord::ZERO.distance(offset), 1);
+        }
+        for (i, &offset) in SECOND_SHELL_OFFSETS.iter().enumerate() {
+            assert!(RhombicCoord::is_valid(offset.x, offset.y, offset.z));
+            assert_eq!(
+                offset + SECOND_SHELL_OFFSETS[(i + 3) % 6],
+                RhombicCoord::ZERO
+            );
+            assert_eq!(RhombicCoord::ZERO.distance(offset), 2);
+        }
+    }
+
+    #[test]
+    fn rings_have_10n2_plus_2_cells() {
+        let center = RhombicCoord::new(3, -1, 2);
+        assert_eq!(center.ring(0).collect::<Vec<_>>(), [center]);
+        for radius in 1..=4 {
+            assert_eq!(center.ring(radius).count() as u32, 10 * radius * radius + 2);
+        }
+    }
+
+    #[test]
+    fn ranges_are_the_union_of_rings() {
+        let center = RhombicCoord::new(-2, 0, 4);
+        for radius in 0..=3 {
+            let mut range = center.range(radius).collect::<Vec<_>>();
+            let mut rings = (0..=radius)
+                .flat_map(|ring| center.ring(ring))
+                .collect::<Vec<_>>();
+            range.sort_unstable();
+            rings.sort_unstable();
+            assert_eq!(range, rings);
+        }
+    }
+
+    #[test]
+    fn distance_matches_breadth_first_search() {
+        let center = RhombicCoord::new(1, 1, -4);
+        let mut distances = std::collections::HashMap::from([(center, 0)]);
+        let mut frontier = vec![center];
+        for step in 1..=3 {
+            frontier = frontier
+                .into_iter()
+                .flat_map(RhombicCoord::neighbors)
+                .filter(|&neighbor| {
+                    if distances.contains_key(&neighbor) {
+                        return false;
+                    }
+                    distances.insert(neighbor, step);
+                    true
+                })
+                .collect();
+        }
+        for (&coord, &steps) in &distances {
+            assert_eq!(center.distance(coord), steps, "{coord:?}");
+            assert_eq!(coord.distance(center), steps, "{coord:?}");
+        }
+        // Everything in the bounds the search didn't reach is further away
+        for coord in center.cells_in_bounds(3) {
+            if !distances.contains_key(&coord) {
+                assert!(center.distance(coord) > 3, "{coord:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn lines_are_symmetric_face_adjacent_shortest_paths() {
+        let start = RhombicCoord::new(1, 0, -1);
+        for a in start.range(3) {
+            for b in start.range(3) {
+                let line = a.line(b);
+                let mut reversed = b.line(a);
+                reversed.reverse();
+                assert_eq!(line, reversed, "{a:?} to {b:?}");
+                assert_eq!(line.len() as u32, a.distance(b) + 1, "{a:?} to {b:?}");
+                assert_eq!((line[0], line[line.len() - 1]), (a, b));
+                for step in line.windows(2) {
+                    assert!(
+                        NEIGHBOR_OFFSETS.contains(&(step[1] - step[0])),
+                        "{a:?} to {b:?} stepped from {:?} to {:?}",
+                        step[0],
+                        step[1]
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lines_break_ties_by_neighbor_index() {
+        let origin = RhombicCoord::ZERO;
+        // Four neighbors are equally close to this line, and (1, 1, 0) comes first
+        let expected = [
+            origin,
+            RhombicCoord::new(1, 1, 0),
+            RhombicCoord::new(2, 0, 0),
+        ];
+        assert_eq!(origin.line(RhombicCoord::new(2, 0, 0)), expected);
+        // (1, 0, 1) and (0, 1, 1) are equally close, and (1, 0, 1) comes first
+        let expected = [
+            origin,
+            RhombicCoord::new(1, 0, 1),
+            RhombicCoord::new(1, 1, 2),
+        ];
+        assert_eq!(origin.line(RhombicCoord::new(1, 1, 2)), expected);
+        // Moving both ends keeps the same shape
+        let offset = RhombicCoord::new(-5, 3, 4);
+        let moved = (origin + offset).line(RhombicCoord::new(1, 1, 2) + offset);
+        assert_eq!(moved, expected.map(|cell| cell + offset));
+    }
+
+    #[test]
+    fn snapped_rotations_are_cell_rotations_and_stay_put() {
+        let same_rotation = |a: Quat, b: Quat| a.dot(b).abs() > 1.0 - 1e-5;
+        let rotations = CellSymmetry::rotations()
+            .map(CellSymmetry::rotation)
+            .collect::<Vec<_>>();
+        for x in -4..=4 {
+            for y in -4..=4 {
+                for z in -4..=4 {
+                    let rotation = Quat::from_euler(
+                        EulerRot::XYZ,
+                        x as f32 * 0.4,
+                        y as f32 * 0.3,
+                        z as f32 * 0.7,
+                    );
+                    let snapped = snap_rotation_to_grid(rotation);
+                    assert!(
+                        rotations.iter().any(|&cell| same_rotation(cell, snapped)),
+                        "{rotation} snapped to {snapped}"
+                    );
+                    assert!(same_rotation(snap_rotation_to_grid(snapped), snapped));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn from_world_matches_voronoi_on_random_points() {
+        let mut state = 0x2545_f491_u32;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as f32 / u32::MAX as f32 * 40.0 - 20.0
+        };
+        for _ in 0..100_000 {
+            let position = Vec3::new(random(), random(), random());
+            let coord = RhombicCoord::from_world(position);
+            assert!(RhombicCoord::is_valid(coord.x, coord.y, coord.z));
+            let distance = coord.center().distance_squared(position);
+            let closest = closest_distance_squared(position);
+            assert!(
+                distance - closest < 1e-4,
+                "{position} went to {coord:?}, {distance} away instead of {closest}"
+            );
+        }
+    }
+
+    #[test]
+    fn from_world_resolves_ties_to_a_closest_cell() {
+        // Every point on the half-integer lattice, which includes every face center, edge
+        // midpoint and vertex of the cells around the origin
+        for x in -8..=8 {
+            for y in -8..=8 {
+                for z in -8..=8 {
+                    let position = Vec3::new(x as f32, y as f32, z as f32) * 0.5;
+                    let coord = RhombicCoord::from_world(position);
+                    assert!(RhombicCoord::is_valid(coord.x, coord.y, coord.z));
+                    assert_eq!(
+                        coord.center().distance_squared(position),
+                        closest_distance_squared(position),
+                        "{position} went to {coord:?}"
+                    );
+                    assert_eq!(RhombicCoord::from_world(position), coord);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn from_world_round_trips_centers() {
+        for coord in RhombicCoord::ZERO.range(4) {
+            assert_eq!(RhombicCoord::from_world(coord.center()), coord);
+            assert_eq!(RhombicCoord::from_center(coord.center()), Some(coord));
+        }
+    }
+}
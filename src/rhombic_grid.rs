@@ -0,0 +1,301 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::RhombicCoord;
+
+/// The number of lattice units along each axis of a chunk. Only half of the `CHUNK_SIZE^3`
+/// integer points in a chunk are cells.
+pub const CHUNK_SIZE: i32 = 16;
+pub(crate) const CHUNK_CELL_COUNT: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE / 2) as usize;
+
+/// Sparse storage of a value per cell, split into chunks so it can be meshed and saved in pieces.
+#[derive(Clone, Debug)]
+pub struct RhombicGrid<T> {
+    chunks: HashMap<IVec3, Chunk<T>>,
+    len: usize,
+    dirty_chunks: HashSet<IVec3>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Chunk<T> {
+    cells: Box<[Option<T>]>,
+    len: usize,
+}
+
+pub fn chunk_of(cell: RhombicCoord) -> IVec3 {
+    IVec3::new(
+        cell.x().div_euclid(CHUNK_SIZE),
+        cell.y().div_euclid(CHUNK_SIZE),
+        cell.z().div_euclid(CHUNK_SIZE),
+    )
+}
+
+pub fn chunk_origin(chunk: IVec3) -> IVec3 {
+    chunk * CHUNK_SIZE
+}
+
+pub(crate) fn cell_index(cell: RhombicCoord) -> usize {
+    let local = cell.as_ivec3() - chunk_origin(chunk_of(cell));
+    // Exactly one of every two consecutive x values is a cell, so halving can't collide
+    ((local.x + local.y * CHUNK_SIZE + local.z * CHUNK_SIZE * CHUNK_SIZE) / 2) as usize
+}
+
+pub(crate) fn cell_at_index(chunk: IVec3, index: usize) -> RhombicCoord {
+    let even = index as i32 * 2;
+    let y = even / CHUNK_SIZE % CHUNK_SIZE;
+    let z = even / (CHUNK_SIZE * CHUNK_SIZE);
+    let x = even % CHUNK_SIZE + (y + z) % 2;
+    RhombicCoord::from_ivec3(chunk_origin(chunk) + IVec3::new(x, y, z)).unwrap()
+}
+
+impl<T> Chunk<T> {
+    fn new() -> Self {
+        Self {
+            cells: std::iter::repeat_with(|| None)
+                .take(CHUNK_CELL_COUNT)
+                .collect(),
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<T> Default for RhombicGrid<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> RhombicGrid<T> {
+    pub fn new() -> Self {
+        Self {
+            chunks: HashMap::default(),
+            len: 0,
+            dirty_chunks: HashSet::default(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, cell: RhombicCoord) -> Option<&T> {
+        self.chunks.get(&chunk_of(cell))?.cells[cell_index(cell)].as_ref()
+    }
+
+    /// Marks the cell's chunk as dirty if the cell is occupied.
+    pub fn get_mut(&mut self, cell: RhombicCoord) -> Option<&mut T> {
+        let chunk = chunk_of(cell);
+        let value = self.chunks.get_mut(&chunk)?.cells[cell_index(cell)].as_mut()?;
+        self.dirty_chunks.insert(chunk);
+        Some(value)
+    }
+
+    pub fn contains(&self, cell: RhombicCoord) -> bool {
+        self.get(cell).is_some()
+    }
+
+    /// Returns the previous value of the cell.
+    pub fn set(&mut self, cell: RhombicCoord, value: T) -> Option<T> {
+        let chunk = self.chunks.entry(chunk_of(cell)).or_insert_with(Chunk::new);
+        let previous = chunk.cells[cell_index(cell)].replace(value);
+        if previous.is_none() {
+            chunk.len += 1;
+            self.len += 1;
+        }
+        self.mark_cell_dirty(cell);
+        previous
+    }
+
+    pub fn remove(&mut self, cell: RhombicCoord) -> Option<T> {
+        let chunk_coord = chunk_of(cell);
+        let chunk = self.chunks.get_mut(&chunk_coord)?;
+        let previous = chunk.cells[cell_index(cell)].take()?;
+        chunk.len -= 1;
+        self.len -= 1;
+        if chunk.is_empty() {
+            self.chunks.remove(&chunk_coord);
+        }
+        self.mark_cell_dirty(cell);
+        Some(previous)
+    }
+
+    pub fn clear(&mut self) {
+        self.dirty_chunks.extend(self.chunks.keys().copied());
+        self.chunks.clear();
+        self.len = 0;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (RhombicCoord, &T)> {
+        self.chunks
+            .keys()
+            .flat_map(move |&chunk| self.iter_chunk(chunk))
+    }
+
+    /// Iterates the occupied cells of one chunk.
+    pub fn iter_chunk(&self, chunk: IVec3) -> impl Iterator<Item = (RhombicCoord, &T)> {
+        self.chunks.get(&chunk).into_iter().flat_map(move |cells| {
+            cells
+                .cells
+                .iter()
+                .enumerate()
+                .filter_map(move |(index, value)| {
+                    Some((cell_at_index(chunk, index), value.as_ref()?))
+                })
+        })
+    }
+
+    /// The chunks containing at least one occupied cell.
+    pub fn chunks(&self) -> impl Iterator<Item = (IVec3, &Chunk<T>)> {
+        self.chunks.iter().map(|(&coord, chunk)| (coord, chunk))
+    }
+
+    pub fn chunk(&self, chunk: IVec3) -> Option<&Chunk<T>> {
+        self.chunks.get(&chunk)
+    }
+
+    /// Removes a whole chunk, returning its occupied cells.
+    pub fn remove_chunk(&mut self, chunk: IVec3) -> Vec<(RhombicCoord, T)> {
+        let Some(cells) = self.chunks.remove(&chunk) else {
+            return vec![];
+        };
+        self.len -= cells.len;
+        self.mark_chunk_and_neighbors_dirty(chunk);
+        Vec::from(cells.cells)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, value)| Some((cell_at_index(chunk, index), value?)))
+            .collect()
+    }
+
+    /// Chunks whose contents, or whose neighbors' cells along the shared border, changed since
+    /// the last [`RhombicGrid::clear_dirty_chunks`]. Dirty chunks may since have become empty.
+    pub fn dirty_chunks(&self) -> impl Iterator<Item = IVec3> + '_ {
+        self.dirty_chunks.iter().copied()
+    }
+
+    pub fn is_chunk_dirty(&self, chunk: IVec3) -> bool {
+        self.dirty_chunks.contains(&chunk)
+    }
+
+    pub fn mark_chunk_dirty(&mut self, chunk: IVec3) {
+        self.dirty_chunks.insert(chunk);
+    }
+
+    pub fn clear_dirty_chunks(&mut self) {
+        self.dirty_chunks.clear();
+    }
+
+    pub fn take_dirty_chunks(&mut self) -> Vec<IVec3> {
+        self.dirty_chunks.drain().collect()
+    }
+
+    fn mark_cell_dirty(&mut self, cell: RhombicCoord) {
+        // Neighboring chunks need remeshing too if the cell is on their border
+        self.dirty_chunks.insert(chunk_of(cell));
+        for neighbor in cell.neighbors() {
+            self.dirty_chunks.insert(chunk_of(neighbor));
+        }
+    }
+
+    fn mark_chunk_and_neighbors_dirty(&mut self, chunk: IVec3) {
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    self.dirty_chunks.insert(chunk + IVec3::new(x, y, z));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cell_index_round_trips() {
+        for chunk in [
+            IVec3::ZERO,
+            IVec3::new(-1, 0, 0),
+            IVec3::new(0, -1, -1),
+            IVec3::new(-3, 2, -5),
+        ] {
+            for index in 0..CHUNK_CELL_COUNT {
+                let cell = cell_at_index(chunk, index);
+                assert_eq!(chunk_of(cell), chunk);
+                assert_eq!(cell_index(cell), index);
+            }
+        }
+
+        for cell in RhombicCoord::new(-CHUNK_SIZE, -1, -1).range(6) {
+            assert_eq!(cell_at_index(chunk_of(cell), cell_index(cell)), cell);
+        }
+    }
+
+    #[test]
+    fn negative_cells_are_stored_separately() {
+        let mut grid = RhombicGrid::new();
+        let cells = RhombicCoord::new(-1, -1, 0).range(3).collect::<Vec<_>>();
+        for (i, &cell) in cells.iter().enumerate() {
+            assert_eq!(grid.set(cell, i), None);
+        }
+        assert_eq!(grid.len(), cells.len());
+        for (i, &cell) in cells.iter().enumerate() {
+            assert_eq!(grid.get(cell), Some(&i));
+        }
+        assert_eq!(grid.iter().count(), cells.len());
+        for &cell in &cells {
+            assert!(grid.remove(cell).is_some());
+        }
+        assert!(grid.is_empty());
+        assert_eq!(grid.chunks().count(), 0);
+    }
+
+    #[test]
+    fn border_cells_dirty_neighbor_chunks() {
+        let mut grid = RhombicGrid::new();
+
+        // Well inside chunk (0, 0, 0)
+        grid.set(RhombicCoord::new(8, 8, 8), ());
+        assert_eq!(grid.take_dirty_chunks(), vec![IVec3::ZERO]);
+
+        // On the low x border, touching chunk (-1, 0, 0) across a face
+        grid.set(RhombicCoord::new(0, 8, 8), ());
+        let mut dirty = grid.take_dirty_chunks();
+        dirty.sort_by_key(|chunk| chunk.to_array());
+        assert_eq!(dirty, vec![IVec3::new(-1, 0, 0), IVec3::ZERO]);
+
+        // In the corner, touching chunks across faces along two axes
+        grid.remove(RhombicCoord::new(0, 0, 8));
+        assert!(grid.dirty_chunks().next().is_none());
+        grid.set(RhombicCoord::new(0, 0, 8), ());
+        let dirty = grid.take_dirty_chunks();
+        for chunk in [
+            IVec3::ZERO,
+            IVec3::new(-1, 0, 0),
+            IVec3::new(0, -1, 0),
+            IVec3::new(-1, -1, 0),
+        ] {
+            assert!(dirty.contains(&chunk), "{chunk} isn't dirty");
+        }
+        assert!(!dirty.contains(&IVec3::new(0, 0, -1)));
+
+        grid.remove_chunk(IVec3::ZERO);
+        assert_eq!(grid.dirty_chunks().count(), 27);
+        assert!(grid.is_empty());
+    }
+}
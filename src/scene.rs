@@ -0,0 +1,855 @@
+use std::{fmt, io, path::Path};
+
+use bevy::{
+    asset::HandleId,
+    ecs::system::SystemState,
+    hierarchy::despawn_with_children_recursive,
+    prelude::*,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        render_resource::{Face, PrimitiveTopology},
+    },
+    utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    CellFaceColors, DisplayableComponent, MeshPrimitive, MeshPrimitives, ShowInUIProperties,
+    ATTRIBUTE_CELL_FACES, FACE_COUNT,
+};
+
+pub const SCENE_VERSION: u32 = 2;
+
+/// Every entity with [`ShowInUIProperties`], along with the meshes and materials they use.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SceneFile {
+    pub version: u32,
+    pub meshes: Vec<SceneMesh>,
+    pub materials: Vec<SceneMaterial>,
+    pub entities: Vec<SceneEntity>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SceneEntity {
+    pub name: String,
+    pub components: Vec<SceneComponent>,
+}
+
+/// A saved [`DisplayableComponent`]. Components that are computed from others are only recorded
+/// as being present, and assets are indices into [`SceneFile::meshes`] and
+/// [`SceneFile::materials`], or `None` if the asset couldn't be saved.
+///
+/// Lights and cameras aren't displayable, but are saved too; loading them adds the rest of the
+/// components their bundles come with.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum SceneComponent {
+    Transform {
+        translation: [f32; 3],
+        rotation: [f32; 4],
+        scale: [f32; 3],
+    },
+    GlobalTransform,
+    Mesh(Option<usize>),
+    Material(Option<usize>),
+    Visibility(SceneVisibility),
+    ComputedVisibility,
+    CellFaceColors([[f32; 4]; FACE_COUNT]),
+    PointLight {
+        color: [f32; 4],
+        intensity: f32,
+        range: f32,
+        radius: f32,
+        shadows_enabled: bool,
+        shadow_depth_bias: f32,
+        shadow_normal_bias: f32,
+    },
+    DirectionalLight {
+        color: [f32; 4],
+        illuminance: f32,
+        shadows_enabled: bool,
+        shadow_depth_bias: f32,
+        shadow_normal_bias: f32,
+    },
+    SpotLight {
+        color: [f32; 4],
+        intensity: f32,
+        range: f32,
+        radius: f32,
+        shadows_enabled: bool,
+        shadow_depth_bias: f32,
+        shadow_normal_bias: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+    Camera {
+        is_active: bool,
+        order: isize,
+        projection: SceneProjection,
+    },
+}
+
+/// A camera's [`Projection`]. Orthographic projections keep only their scale and clipping planes,
+/// and use the default scaling mode when loaded.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum SceneProjection {
+    Perspective { fov: f32, near: f32, far: f32 },
+    Orthographic { scale: f32, near: f32, far: f32 },
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum SceneVisibility {
+    Inherited,
+    Hidden,
+    Visible,
+}
+
+/// A triangle list mesh with the vertex attributes the built in meshes use.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SceneMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Option<Vec<[f32; 3]>>,
+    pub uvs: Option<Vec<[f32; 2]>>,
+    pub tangents: Option<Vec<[f32; 4]>>,
+    pub colors: Option<Vec<[f32; 4]>>,
+    /// [`ATTRIBUTE_CELL_FACES`], missing from scenes saved before it existed.
+    #[serde(default)]
+    pub cell_faces: Option<Vec<u32>>,
+    pub indices: Option<Vec<u32>>,
+    /// The [`MeshPrimitive`] the mesh was generated from, if it was, so it can still be edited
+    /// as one. Missing from scenes saved before it existed.
+    #[serde(default)]
+    pub primitive: Option<MeshPrimitive>,
+}
+
+/// The parameters of a [`StandardMaterial`]. Textures aren't saved.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SceneMaterial {
+    pub base_color: [f32; 4],
+    pub emissive: [f32; 4],
+    pub perceptual_roughness: f32,
+    pub metallic: f32,
+    pub reflectance: f32,
+    pub flip_normal_map_y: bool,
+    pub double_sided: bool,
+    pub cull_mode: Option<SceneFace>,
+    pub unlit: bool,
+    pub fog_enabled: bool,
+    pub alpha_mode: SceneAlphaMode,
+    pub depth_bias: f32,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum SceneFace {
+    Front,
+    Back,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum SceneAlphaMode {
+    Opaque,
+    Mask(f32),
+    Blend,
+    Premultiplied,
+    Add,
+    Multiply,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(io::Error),
+    Serialize(ron::Error),
+    Deserialize(ron::error::SpannedError),
+    UnsupportedVersion(u32),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(error) => write!(f, "{error}"),
+            SceneError::Serialize(error) => write!(f, "{error}"),
+            SceneError::Deserialize(error) => write!(f, "{error}"),
+            SceneError::UnsupportedVersion(version) => write!(
+                f,
+                "scene version {version} is newer than the supported version {SCENE_VERSION}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<io::Error> for SceneError {
+    fn from(error: io::Error) -> Self {
+        SceneError::Io(error)
+    }
+}
+
+impl From<ron::Error> for SceneError {
+    fn from(error: ron::Error) -> Self {
+        SceneError::Serialize(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for SceneError {
+    fn from(error: ron::error::SpannedError) -> Self {
+        SceneError::Deserialize(error)
+    }
+}
+
+impl SceneFile {
+    pub fn to_ron(&self) -> Result<String, SceneError> {
+        Ok(ron::ser::to_string_pretty(
+            self,
+            ron::ser::PrettyConfig::default(),
+        )?)
+    }
+
+    pub fn from_ron(ron: &str) -> Result<Self, SceneError> {
+        let scene: Self = ron::from_str(ron)?;
+        if scene.version > SCENE_VERSION {
+            return Err(SceneError::UnsupportedVersion(scene.version));
+        }
+        Ok(scene)
+    }
+}
+
+/// Gives [`DisplayableComponent::save`] a way to store the assets a component refers to, saving
+/// each asset only once.
+pub struct SceneSaver<'w> {
+    world: &'w World,
+    meshes: Vec<SceneMesh>,
+    materials: Vec<SceneMaterial>,
+    mesh_indices: HashMap<HandleId, Option<usize>>,
+    material_indices: HashMap<HandleId, Option<usize>>,
+}
+
+impl<'w> SceneSaver<'w> {
+    pub fn mesh(&mut self, handle: &Handle<Mesh>) -> Option<usize> {
+        let world = self.world;
+        let meshes = &mut self.meshes;
+        *self.mesh_indices.entry(handle.id()).or_insert_with(|| {
+            let mut mesh = SceneMesh::from_mesh(world.resource::<Assets<Mesh>>().get(handle)?)?;
+            mesh.primitive = world
+                .get_resource::<MeshPrimitives>()
+                .and_then(|primitives| primitives.get(handle));
+            meshes.push(mesh);
+            Some(meshes.len() - 1)
+        })
+    }
+
+    pub fn material(&mut self, handle: &Handle<StandardMaterial>) -> Option<usize> {
+        let world = self.world;
+        let materials = &mut self.materials;
+        *self.material_indices.entry(handle.id()).or_insert_with(|| {
+            let material = world.resource::<Assets<StandardMaterial>>().get(handle)?;
+            materials.push(SceneMaterial::from_material(material));
+            Some(materials.len() - 1)
+        })
+    }
+}
+
+pub fn save_scene(world: &mut World) -> SceneFile {
+    let mut system_state =
+        SystemState::<Query<(Entity, &ShowInUIProperties, &dyn DisplayableComponent)>>::new(world);
+    let query = system_state.get(world);
+
+    let mut saver = SceneSaver {
+        world,
+        meshes: vec![],
+        materials: vec![],
+        mesh_indices: HashMap::default(),
+        material_indices: HashMap::default(),
+    };
+    let entities = query
+        .iter()
+        .map(
+            |(entity, ui_properties, displayable_components)| SceneEntity {
+                name: ui_properties.name.clone(),
+                components: displayable_components
+                    .iter()
+                    .map(|displayable_component| displayable_component.save(&mut saver))
+                    .chain(save_lights_and_cameras(world, entity))
+                    .collect(),
+            },
+        )
+        .collect();
+
+    SceneFile {
+        version: SCENE_VERSION,
+        meshes: saver.meshes,
+        materials: saver.materials,
+        entities,
+    }
+}
+
+fn save_lights_and_cameras(world: &World, entity: Entity) -> Vec<SceneComponent> {
+    let mut components = vec![];
+    if let Some(light) = world.get::<PointLight>(entity) {
+        components.push(SceneComponent::PointLight {
+            color: light.color.as_rgba_f32(),
+            intensity: light.intensity,
+            range: light.range,
+            radius: light.radius,
+            shadows_enabled: light.shadows_enabled,
+            shadow_depth_bias: light.shadow_depth_bias,
+            shadow_normal_bias: light.shadow_normal_bias,
+        });
+    }
+    if let Some(light) = world.get::<DirectionalLight>(entity) {
+        components.push(SceneComponent::DirectionalLight {
+            color: light.color.as_rgba_f32(),
+            illuminance: light.illuminance,
+            shadows_enabled: light.shadows_enabled,
+            shadow_depth_bias: light.shadow_depth_bias,
+            shadow_normal_bias: light.shadow_normal_bias,
+        });
+    }
+    if let Some(light) = world.get::<SpotLight>(entity) {
+        components.push(SceneComponent::SpotLight {
+            color: light.color.as_rgba_f32(),
+            intensity: light.intensity,
+            range: light.range,
+            radius: light.radius,
+            shadows_enabled: light.shadows_enabled,
+            shadow_depth_bias: light.shadow_depth_bias,
+            shadow_normal_bias: light.shadow_normal_bias,
+            inner_angle: light.inner_angle,
+            outer_angle: light.outer_angle,
+        });
+    }
+    if let Some(camera) = world.get::<Camera>(entity) {
+        let projection = match world.get::<Projection>(entity) {
+            Some(Projection::Orthographic(projection)) => SceneProjection::Orthographic {
+                scale: projection.scale,
+                near: projection.near,
+                far: projection.far,
+            },
+            Some(Projection::Perspective(projection)) => SceneProjection::Perspective {
+                fov: projection.fov,
+                near: projection.near,
+                far: projection.far,
+            },
+            None => {
+                let projection = PerspectiveProjection::default();
+                SceneProjection::Perspective {
+                    fov: projection.fov,
+                    near: projection.near,
+                    far: projection.far,
+                }
+            }
+        };
+        components.push(SceneComponent::Camera {
+            is_active: camera.is_active,
+            order: camera.order,
+            projection,
+        });
+    }
+    components
+}
+
+/// Replaces every entity with [`ShowInUIProperties`], and their children, with the scene's.
+///
+/// A scene holds the whole editable scene, like [`save_scene`] saves it, so entities the scene
+/// didn't create are despawned too. Entities without [`ShowInUIProperties`], like the editor's own
+/// gizmos, are left alone.
+pub fn load_scene(world: &mut World, scene: &SceneFile) {
+    let entities = world
+        .query_filtered::<Entity, With<ShowInUIProperties>>()
+        .iter(world)
+        .collect::<Vec<_>>();
+    for entity in entities {
+        despawn_with_children_recursive(world, entity);
+    }
+
+    let meshes = {
+        let mut assets = world.resource_mut::<Assets<Mesh>>();
+        scene
+            .meshes
+            .iter()
+            .map(|mesh| assets.add(mesh.to_mesh()))
+            .collect::<Vec<_>>()
+    };
+    let mut primitives = world.resource_mut::<MeshPrimitives>();
+    for (handle, mesh) in meshes.iter().zip(&scene.meshes) {
+        if let Some(primitive) = mesh.primitive {
+            primitives.insert(handle, primitive);
+        }
+    }
+    let materials = {
+        let mut assets = world.resource_mut::<Assets<StandardMaterial>>();
+        scene
+            .materials
+            .iter()
+            .map(|material| assets.add(material.to_material()))
+            .collect::<Vec<_>>()
+    };
+
+    for scene_entity in &scene.entities {
+        let mut entity = world.spawn(ShowInUIProperties::new(scene_entity.name.clone()));
+        for component in &scene_entity.components {
+            match *component {
+                SceneComponent::Transform {
+                    translation,
+                    rotation,
+                    scale,
+                } => {
+                    entity.insert(Transform {
+                        translation: translation.into(),
+                        rotation: Quat::from_array(rotation),
+                        scale: scale.into(),
+                    });
+                }
+                SceneComponent::GlobalTransform => {
+                    entity.insert(GlobalTransform::default());
+                }
+                SceneComponent::Mesh(index) => {
+                    let mesh = index.and_then(|index| meshes.get(index));
+                    entity.insert(mesh.cloned().unwrap_or_default());
+                }
+                SceneComponent::Material(index) => {
+                    let material = index.and_then(|index| materials.get(index));
+                    entity.insert(material.cloned().unwrap_or_default());
+                }
+                SceneComponent::Visibility(visibility) => {
+                    entity.insert(match visibility {
+                        SceneVisibility::Inherited => Visibility::Inherited,
+                        SceneVisibility::Hidden => Visibility::Hidden,
+                        SceneVisibility::Visible => Visibility::Visible,
+                    });
+                }
+                SceneComponent::ComputedVisibility => {
+                    entity.insert(ComputedVisibility::default());
+                }
+                SceneComponent::CellFaceColors(colors) => {
+                    entity.insert(CellFaceColors(colors.map(Color::from)));
+                }
+                SceneComponent::PointLight {
+                    color,
+                    intensity,
+                    range,
+                    radius,
+                    shadows_enabled,
+                    shadow_depth_bias,
+                    shadow_normal_bias,
+                } => {
+                    let PointLightBundle {
+                        cubemap_visible_entities,
+                        cubemap_frusta,
+                        ..
+                    } = default();
+                    entity.insert((
+                        PointLight {
+                            color: Color::from(color),
+                            intensity,
+                            range,
+                            radius,
+                            shadows_enabled,
+                            shadow_depth_bias,
+                            shadow_normal_bias,
+                        },
+                        cubemap_visible_entities,
+                        cubemap_frusta,
+                    ));
+                }
+                SceneComponent::DirectionalLight {
+                    color,
+                    illuminance,
+                    shadows_enabled,
+                    shadow_depth_bias,
+                    shadow_normal_bias,
+                } => {
+                    let DirectionalLightBundle {
+                        frusta,
+                        cascades,
+                        cascade_shadow_config,
+                        visible_entities,
+                        ..
+                    } = default();
+                    entity.insert((
+                        DirectionalLight {
+                            color: Color::from(color),
+                            illuminance,
+                            shadows_enabled,
+                            shadow_depth_bias,
+                            shadow_normal_bias,
+                        },
+                        frusta,
+                        cascades,
+                        cascade_shadow_config,
+                        visible_entities,
+                    ));
+                }
+                SceneComponent::SpotLight {
+                    color,
+                    intensity,
+                    range,
+                    radius,
+                    shadows_enabled,
+                    shadow_depth_bias,
+                    shadow_normal_bias,
+                    inner_angle,
+                    outer_angle,
+                } => {
+                    let SpotLightBundle {
+                        visible_entities,
+                        frustum,
+                        ..
+                    } = default();
+                    entity.insert((
+                        SpotLight {
+                            color: Color::from(color),
+                            intensity,
+                            range,
+                            radius,
+                            shadows_enabled,
+                            shadow_depth_bias,
+                            shadow_normal_bias,
+                            inner_angle,
+                            outer_angle,
+                        },
+                        visible_entities,
+                        frustum,
+                    ));
+                }
+                SceneComponent::Camera {
+                    is_active,
+                    order,
+                    projection,
+                } => {
+                    let Camera3dBundle {
+                        camera,
+                        camera_render_graph,
+                        visible_entities,
+                        frustum,
+                        camera_3d,
+                        tonemapping,
+                        dither,
+                        color_grading,
+                        ..
+                    } = default();
+                    let projection = match projection {
+                        SceneProjection::Perspective { fov, near, far } => {
+                            Projection::Perspective(PerspectiveProjection {
+                                fov,
+                                near,
+                                far,
+                                ..default()
+                            })
+                        }
+                        SceneProjection::Orthographic { scale, near, far } => {
+                            Projection::Orthographic(OrthographicProjection {
+                                scale,
+                                near,
+                                far,
+                                ..default()
+                            })
+                        }
+                    };
+                    entity.insert((
+                        Camera {
+                            is_active,
+                            order,
+                            ..camera
+                        },
+                        camera_render_graph,
+                        projection,
+                        visible_entities,
+                        frustum,
+                        camera_3d,
+                        tonemapping,
+                        dither,
+                        color_grading,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+pub fn save_scene_to_file(world: &mut World, path: impl AsRef<Path>) -> Result<(), SceneError> {
+    let ron = save_scene(world).to_ron()?;
+    std::fs::write(path, ron)?;
+    Ok(())
+}
+
+pub fn load_scene_from_file(world: &mut World, path: impl AsRef<Path>) -> Result<(), SceneError> {
+    let scene = SceneFile::from_ron(&std::fs::read_to_string(path)?)?;
+    load_scene(world, &scene);
+    Ok(())
+}
+
+impl SceneMesh {
+    /// Returns `None` for meshes that aren't triangle lists.
+    pub fn from_mesh(mesh: &Mesh) -> Option<Self> {
+        if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+            return None;
+        }
+        let float3 = |attribute| match mesh.attribute(attribute) {
+            Some(VertexAttributeValues::Float32x3(values)) => Some(values.clone()),
+            _ => None,
+        };
+        let float4 = |attribute| match mesh.attribute(attribute) {
+            Some(VertexAttributeValues::Float32x4(values)) => Some(values.clone()),
+            _ => None,
+        };
+        Some(Self {
+            positions: float3(Mesh::ATTRIBUTE_POSITION)?,
+            normals: float3(Mesh::ATTRIBUTE_NORMAL),
+            uvs: match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+                Some(VertexAttributeValues::Float32x2(values)) => Some(values.clone()),
+                _ => None,
+            },
+            tangents: float4(Mesh::ATTRIBUTE_TANGENT),
+            colors: float4(Mesh::ATTRIBUTE_COLOR),
+            cell_faces: match mesh.attribute(ATTRIBUTE_CELL_FACES) {
+                Some(VertexAttributeValues::Uint32(values)) => Some(values.clone()),
+                _ => None,
+            },
+            indices: mesh
+                .indices()
+                .map(|indices| indices.iter().map(|index| index as u32).collect()),
+            primitive: None,
+        })
+    }
+
+    pub fn to_mesh(&self) -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions.clone());
+        if let Some(normals) = &self.normals {
+            mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals.clone());
+        }
+        if let Some(uvs) = &self.uvs {
+            mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs.clone());
+        }
+        if let Some(tangents) = &self.tangents {
+            mesh.insert_attribute(Mesh::ATTRIBUTE_TANGENT, tangents.clone());
+        }
+        if let Some(colors) = &self.colors {
+            mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors.clone());
+        }
+        if let Some(cell_faces) = &self.cell_faces {
+            mesh.insert_attribute(ATTRIBUTE_CELL_FACES, cell_faces.clone());
+        }
+        if let Some(indices) = &self.indices {
+            mesh.set_indices(Some(Indices::U32(indices.clone())));
+        }
+        mesh
+    }
+}
+
+impl SceneMaterial {
+    pub fn from_material(material: &StandardMaterial) -> Self {
+        Self {
+            base_color: material.base_color.as_rgba_f32(),
+            emissive: material.emissive.as_rgba_f32(),
+            perceptual_roughness: material.perceptual_roughness,
+            metallic: material.metallic,
+            reflectance: material.reflectance,
+            flip_normal_map_y: material.flip_normal_map_y,
+            double_sided: material.double_sided,
+            cull_mode: material.cull_mode.map(|face| match face {
+                Face::Front => SceneFace::Front,
+                Face::Back => SceneFace::Back,
+            }),
+            unlit: material.unlit,
+            fog_enabled: material.fog_enabled,
+            alpha_mode: match material.alpha_mode {
+                AlphaMode::Opaque => SceneAlphaMode::Opaque,
+                AlphaMode::Mask(cutoff) => SceneAlphaMode::Mask(cutoff),
+                AlphaMode::Blend => SceneAlphaMode::Blend,
+                AlphaMode::Premultiplied => SceneAlphaMode::Premultiplied,
+                AlphaMode::Add => SceneAlphaMode::Add,
+                AlphaMode::Multiply => SceneAlphaMode::Multiply,
+            },
+            depth_bias: material.depth_bias,
+        }
+    }
+
+    pub fn to_material(&self) -> StandardMaterial {
+        StandardMaterial {
+            base_color: Color::from(self.base_color),
+            emissive: Color::from(self.emissive),
+            perceptual_roughness: self.perceptual_roughness,
+            metallic: self.metallic,
+            reflectance: self.reflectance,
+            flip_normal_map_y: self.flip_normal_map_y,
+            double_sided: self.double_sided,
+            cull_mode: self.cull_mode.map(|face| match face {
+                SceneFace::Front => Face::Front,
+                SceneFace::Back => Face::Back,
+            }),
+            unlit: self.unlit,
+            fog_enabled: self.fog_enabled,
+            alpha_mode: match self.alpha_mode {
+                SceneAlphaMode::Opaque => AlphaMode::Opaque,
+                SceneAlphaMode::Mask(cutoff) => AlphaMode::Mask(cutoff),
+                SceneAlphaMode::Blend => AlphaMode::Blend,
+                SceneAlphaMode::Premultiplied => AlphaMode::Premultiplied,
+                SceneAlphaMode::Add => AlphaMode::Add,
+                SceneAlphaMode::Multiply => AlphaMode::Multiply,
+            },
+            depth_bias: self.depth_bias,
+            ..default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{pbr::Cascades, render::primitives::CubemapFrusta};
+
+    use super::*;
+    use crate::{add_primitive_mesh, RegisterDisplayableComponentExt};
+
+    fn scene_app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin::default())
+            .add_asset::<Mesh>()
+            .add_asset::<StandardMaterial>()
+            .init_resource::<MeshPrimitives>()
+            .register_displayable_component::<Transform>()
+            .register_displayable_component::<GlobalTransform>()
+            .register_displayable_component::<Handle<Mesh>>()
+            .register_displayable_component::<Handle<StandardMaterial>>()
+            .register_displayable_component::<Visibility>()
+            .register_displayable_component::<ComputedVisibility>()
+            .register_displayable_component::<CellFaceColors>();
+        app
+    }
+
+    #[test]
+    fn scenes_round_trip() {
+        let mut app = scene_app();
+        let world = &mut app.world;
+        let mesh = add_primitive_mesh(
+            world,
+            MeshPrimitive::RhombicDodecahedron {
+                size: 1.0,
+                bevel: 0.1,
+                inset: 0.0,
+            },
+        );
+        let material = world
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(Color::rgb(0.2, 0.4, 0.6).into());
+        world.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material,
+                transform: Transform::from_xyz(1.0, 2.0, 3.0)
+                    .with_rotation(Quat::from_rotation_y(0.5)),
+                ..default()
+            },
+            CellFaceColors::from_directions(),
+            ShowInUIProperties::new("Cell".to_string()),
+        ));
+        world.spawn((
+            PbrBundle {
+                mesh,
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            ShowInUIProperties::new("Same Mesh".to_string()),
+        ));
+        world.spawn((
+            PointLightBundle {
+                point_light: PointLight {
+                    intensity: 1234.0,
+                    shadows_enabled: true,
+                    ..default()
+                },
+                ..default()
+            },
+            ShowInUIProperties::new("Point Light".to_string()),
+        ));
+        world.spawn((
+            DirectionalLightBundle::default(),
+            ShowInUIProperties::new("Directional Light".to_string()),
+        ));
+        world.spawn((
+            SpotLightBundle {
+                spot_light: SpotLight {
+                    outer_angle: 0.75,
+                    ..default()
+                },
+                ..default()
+            },
+            ShowInUIProperties::new("Spot Light".to_string()),
+        ));
+        world.spawn((
+            Camera3dBundle {
+                camera: Camera {
+                    is_active: false,
+                    order: 2,
+                    ..default()
+                },
+                projection: OrthographicProjection {
+                    scale: 3.0,
+                    ..default()
+                }
+                .into(),
+                ..default()
+            },
+            ShowInUIProperties::new("Camera".to_string()),
+        ));
+        // Loading can put the entities in a different order
+        let save_sorted = |world: &mut World| {
+            let mut scene = save_scene(world);
+            scene.entities.sort_by(|a, b| a.name.cmp(&b.name));
+            scene.to_ron().unwrap()
+        };
+        let ron = save_sorted(world);
+
+        let mut loaded = scene_app();
+        let scene = SceneFile::from_ron(&ron).unwrap();
+        assert_eq!(scene.meshes.len(), 1);
+        assert!(scene.meshes[0].primitive.is_some());
+        assert_eq!(scene.materials.len(), 1);
+        // Loading replaces everything that was being edited
+        loaded.world.spawn((
+            TransformBundle::default(),
+            ShowInUIProperties::new("Not In The Scene".to_string()),
+        ));
+        load_scene(&mut loaded.world, &scene);
+        assert_eq!(save_sorted(&mut loaded.world), ron);
+
+        let world = &mut loaded.world;
+        let lights = world
+            .query_filtered::<&PointLight, With<CubemapFrusta>>()
+            .iter(world)
+            .map(|light| light.intensity)
+            .collect::<Vec<_>>();
+        assert_eq!(lights, [1234.0]);
+        assert_eq!(
+            world
+                .query_filtered::<(), (With<DirectionalLight>, With<Cascades>)>()
+                .iter(world)
+                .count(),
+            1
+        );
+        let (camera, projection) = world
+            .query_filtered::<(&Camera, &Projection), With<Camera3d>>()
+            .single(world);
+        assert!(!camera.is_active);
+        assert_eq!(camera.order, 2);
+        assert!(matches!(
+            projection,
+            Projection::Orthographic(projection) if projection.scale == 3.0
+        ));
+    }
+
+    #[test]
+    fn newer_scenes_are_rejected() {
+        let scene = SceneFile {
+            version: SCENE_VERSION + 1,
+            meshes: vec![],
+            materials: vec![],
+            entities: vec![],
+        };
+        assert!(matches!(
+            SceneFile::from_ron(&scene.to_ron().unwrap()),
+            Err(SceneError::UnsupportedVersion(_))
+        ));
+    }
+}
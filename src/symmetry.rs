@@ -0,0 +1,293 @@
+use bevy::prelude::*;
+
+use crate::{RhombicCoord, NEIGHBOR_OFFSETS};
+
+const PERMUTATIONS: [[u8; 3]; 6] = [
+    [0, 1, 2],
+    [1, 2, 0],
+    [2, 0, 1],
+    [0, 2, 1],
+    [2, 1, 0],
+    [1, 0, 2],
+];
+
+/// One of the 48 symmetries of a cell: the 24 rotations of a cube, and those rotations
+/// followed by a mirror.
+///
+/// Every symmetry permutes the axes and flips some of them, which maps the lattice onto itself,
+/// so symmetries can be applied to [`RhombicCoord`]s directly. They are about the origin; to turn
+/// a shape about another cell, subtract it first and add it back after.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CellSymmetry {
+    /// Axis `i` of the result is axis `axes[i]` of the input, negated if `signs[i]` is negative.
+    axes: [u8; 3],
+    signs: [i8; 3],
+}
+
+impl Default for CellSymmetry {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl CellSymmetry {
+    pub const IDENTITY: Self = Self {
+        axes: [0, 1, 2],
+        signs: [1, 1, 1],
+    };
+
+    /// Maps every cell to the one on the opposite side of the origin, and face `i` to face
+    /// `(i + 6) % 12`.
+    pub const INVERSION: Self = Self {
+        axes: [0, 1, 2],
+        signs: [-1, -1, -1],
+    };
+
+    /// All 48 symmetries, starting with [`CellSymmetry::IDENTITY`].
+    pub fn all() -> impl Iterator<Item = Self> {
+        PERMUTATIONS.into_iter().flat_map(|axes| {
+            (0..8).map(move |flips: u8| Self {
+                axes,
+                signs: [0, 1, 2].map(|i| if flips & (1 << i) == 0 { 1 } else { -1 }),
+            })
+        })
+    }
+
+    /// The 24 symmetries that are rotations.
+    pub fn rotations() -> impl Iterator<Item = Self> {
+        Self::all().filter(|symmetry| !symmetry.is_reflection())
+    }
+
+    /// The rotation closest to `rotation`.
+    pub fn nearest_rotation(rotation: Quat) -> Self {
+        Self::rotations()
+            .max_by(|a, b| {
+                let a = a.rotation().dot(rotation).abs();
+                let b = b.rotation().dot(rotation).abs();
+                a.total_cmp(&b)
+            })
+            .unwrap()
+    }
+
+    /// Whether this symmetry mirrors cells, i.e. isn't a rotation.
+    pub fn is_reflection(self) -> bool {
+        let permutation = PERMUTATIONS
+            .iter()
+            .position(|&permutation| permutation == self.axes)
+            .unwrap();
+        // The first three permutations are even, the last three are single swaps
+        let sign = if permutation < 3 { 1 } else { -1 } * self.signs.iter().product::<i8>();
+        sign < 0
+    }
+
+    pub fn inverse(self) -> Self {
+        let mut inverse = Self::IDENTITY;
+        for i in 0..3 {
+            inverse.axes[self.axes[i] as usize] = i as u8;
+            inverse.signs[self.axes[i] as usize] = self.signs[i];
+        }
+        inverse
+    }
+
+    pub fn apply_ivec3(self, v: IVec3) -> IVec3 {
+        IVec3::from_array([0, 1, 2].map(|i| v[self.axes[i] as usize] * self.signs[i] as i32))
+    }
+
+    pub fn apply_vec3(self, v: Vec3) -> Vec3 {
+        Vec3::from_array([0, 1, 2].map(|i| v[self.axes[i] as usize] * self.signs[i] as f32))
+    }
+
+    pub fn apply_coord(self, coord: RhombicCoord) -> RhombicCoord {
+        // Permuting and negating coordinates keeps their sum even
+        RhombicCoord::from_ivec3(self.apply_ivec3(coord.as_ivec3())).unwrap()
+    }
+
+    /// Where face `face`, indexed like [`NEIGHBOR_OFFSETS`], ends up.
+    pub fn apply_face(self, face: usize) -> usize {
+        let offset = self.apply_coord(NEIGHBOR_OFFSETS[face]);
+        NEIGHBOR_OFFSETS
+            .iter()
+            .position(|&neighbor| neighbor == offset)
+            .unwrap()
+    }
+
+    /// Turns an orientation by this symmetry, `self.rotation() * rotation`.
+    pub fn apply_quat(self, rotation: Quat) -> Quat {
+        self.rotation() * rotation
+    }
+
+    pub fn to_mat3(self) -> Mat3 {
+        Mat3::from_cols(
+            self.apply_vec3(Vec3::X),
+            self.apply_vec3(Vec3::Y),
+            self.apply_vec3(Vec3::Z),
+        )
+    }
+
+    /// The rotation part of this symmetry. A reflection is the rotation followed by
+    /// [`CellSymmetry::INVERSION`], which a [`Quat`] can't represent; since a cell is its own
+    /// inversion the shape still lines up, but its faces are swapped with their opposites.
+    pub fn rotation(self) -> Quat {
+        let rotation = if self.is_reflection() {
+            self * Self::INVERSION
+        } else {
+            self
+        };
+        Quat::from_mat3(&rotation.to_mat3())
+    }
+}
+
+impl std::ops::Mul for CellSymmetry {
+    type Output = Self;
+
+    /// `(a * b)` applies `b` first, then `a`.
+    fn mul(self, rhs: Self) -> Self {
+        Self {
+            axes: self.axes.map(|axis| rhs.axes[axis as usize]),
+            signs: [0, 1, 2].map(|i| self.signs[i] * rhs.signs[self.axes[i] as usize]),
+        }
+    }
+}
+
+impl std::ops::MulAssign for CellSymmetry {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+
+/// The same cells for every shape that is a rotated, mirrored or moved copy of `cells`, for
+/// finding repeated patterns.
+///
+/// Returns the sorted canonical cells, along with the symmetry and offset that give them:
+/// every canonical cell is `symmetry.apply_coord(cell) + offset` for one of `cells`. Of all the
+/// symmetries, the one whose moved and sorted cells compare least is chosen, with the least
+/// cell moved to the origin.
+pub fn canonicalize_cells(
+    cells: impl IntoIterator<Item = RhombicCoord>,
+) -> (Vec<RhombicCoord>, CellSymmetry, RhombicCoord) {
+    let cells = cells.into_iter().collect::<Vec<_>>();
+    CellSymmetry::all()
+        .map(|symmetry| {
+            let mut transformed = cells
+                .iter()
+                .map(|&cell| symmetry.apply_coord(cell))
+                .collect::<Vec<_>>();
+            transformed.sort_unstable();
+            transformed.dedup();
+            let offset = transformed
+                .first()
+                .map_or(RhombicCoord::ZERO, |&first| -first);
+            for cell in &mut transformed {
+                *cell += offset;
+            }
+            (transformed, symmetry, offset)
+        })
+        .min_by(|(a, ..), (b, ..)| a.cmp(b))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FACE_NORMALS;
+
+    /// The face whose outward normal is `normal`.
+    fn face_facing(normal: Vec3) -> usize {
+        FACE_NORMALS
+            .iter()
+            .position(|face| face.abs_diff_eq(normal, 1e-5))
+            .unwrap_or_else(|| panic!("no face faces {normal}"))
+    }
+
+    #[test]
+    fn symmetries_are_a_group_of_48() {
+        let all = CellSymmetry::all().collect::<Vec<_>>();
+        assert_eq!(all.len(), 48);
+        assert_eq!(all[0], CellSymmetry::IDENTITY);
+        for (i, a) in all.iter().enumerate() {
+            assert!(!all[..i].contains(a), "{a:?} is repeated");
+        }
+        for &a in &all {
+            assert_eq!(a * a.inverse(), CellSymmetry::IDENTITY);
+            assert_eq!(a.inverse() * a, CellSymmetry::IDENTITY);
+            assert_eq!(a * CellSymmetry::IDENTITY, a);
+            for &b in &all {
+                assert!(all.contains(&(a * b)), "{a:?} * {b:?} isn't a symmetry");
+                // Composing applies the right hand side first
+                let v = Vec3::new(1.0, 2.0, 3.0);
+                assert_eq!((a * b).apply_vec3(v), a.apply_vec3(b.apply_vec3(v)));
+            }
+        }
+    }
+
+    #[test]
+    fn half_of_the_symmetries_are_rotations() {
+        assert_eq!(CellSymmetry::rotations().count(), 24);
+        for symmetry in CellSymmetry::all() {
+            let determinant = symmetry.to_mat3().determinant();
+            assert_eq!(symmetry.is_reflection(), determinant < 0.0, "{symmetry:?}");
+        }
+        assert!(CellSymmetry::INVERSION.is_reflection());
+    }
+
+    #[test]
+    fn symmetries_keep_cells_and_distances() {
+        let cells = RhombicCoord::new(1, -1, 2).range(2).collect::<Vec<_>>();
+        for symmetry in CellSymmetry::all() {
+            for &a in &cells {
+                let moved = symmetry.apply_ivec3(a.as_ivec3());
+                assert!(RhombicCoord::is_valid(moved.x, moved.y, moved.z));
+                for &b in &cells {
+                    let distance = symmetry.apply_coord(a).distance(symmetry.apply_coord(b));
+                    assert_eq!(distance, a.distance(b), "{symmetry:?} on {a:?} and {b:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn faces_follow_their_normals() {
+        for symmetry in CellSymmetry::all() {
+            for (face, &normal) in FACE_NORMALS.iter().enumerate() {
+                let moved = symmetry.apply_face(face);
+                assert_eq!(moved, face_facing(symmetry.to_mat3() * normal));
+                // A reflection's rotation lands on the opposite face
+                let rotated = symmetry.apply_quat(Quat::IDENTITY) * normal;
+                let expected = if symmetry.is_reflection() {
+                    (moved + 6) % 12
+                } else {
+                    moved
+                };
+                assert_eq!(
+                    face_facing(rotated),
+                    expected,
+                    "{symmetry:?} on face {face}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn canonical_cells_ignore_symmetry_and_position() {
+        let shape = [
+            RhombicCoord::new(0, 0, 0),
+            RhombicCoord::new(1, 1, 0),
+            RhombicCoord::new(2, 0, 0),
+            RhombicCoord::new(2, 1, 1),
+            RhombicCoord::new(0, -1, 3),
+        ];
+        let (canonical, ..) = canonicalize_cells(shape);
+        for symmetry in CellSymmetry::all() {
+            for offset in [RhombicCoord::ZERO, RhombicCoord::new(-3, 7, 2)] {
+                let moved = shape.map(|cell| symmetry.apply_coord(cell) + offset);
+                let (cells, found, found_offset) = canonicalize_cells(moved);
+                assert_eq!(cells, canonical, "{symmetry:?} and {offset:?}");
+                let mut rebuilt = moved
+                    .map(|cell| found.apply_coord(cell) + found_offset)
+                    .to_vec();
+                rebuilt.sort_unstable();
+                assert_eq!(rebuilt, canonical);
+            }
+        }
+    }
+}
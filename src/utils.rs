@@ -1,40 +1,561 @@
-use bevy::prelude::*;
+use bevy::{
+    asset::HandleId,
+    prelude::*,
+    render::{
+        mesh::{Indices, MeshVertexAttribute},
+        render_resource::{PrimitiveTopology, VertexFormat},
+    },
+    utils::HashMap,
+};
+use serde::{Deserialize, Serialize};
 
+use crate::{
+    CELL_VERTICES, EDGE_FACES, EDGE_VERTICES, FACE_COUNT, FACE_NORMALS, FACE_VERTICES, VERTEX_FACES,
+};
+
+/// Which faces of a cell, indexed like [`crate::NEIGHBOR_OFFSETS`], each vertex of a cell mesh lies
+/// on, as a bitmask. Vertices on a corner or edge, or on a beveled corner or edge, have a bit for
+/// every face meeting there.
+pub const ATTRIBUTE_CELL_FACES: MeshVertexAttribute =
+    MeshVertexAttribute::new("Vertex_CellFaces", 1_872_263_909, VertexFormat::Uint32);
+
+/// Vertex colors for the vertices of a cell mesh from their [`ATTRIBUTE_CELL_FACES`], mixing the
+/// colors of every face a vertex lies on.
+pub fn cell_face_vertex_colors(faces: &[u32], colors: &[Color; FACE_COUNT]) -> Vec<[f32; 4]> {
+    faces
+        .iter()
+        .map(|&faces| {
+            let (sum, count) = (0..FACE_COUNT)
+                .filter(|face| faces & (1 << face) != 0)
+                .fold((Vec4::ZERO, 0), |(sum, count), face| {
+                    (
+                        sum + Vec4::from(colors[face].as_linear_rgba_f32()),
+                        count + 1,
+                    )
+                });
+            (sum / count.max(1) as f32).to_array()
+        })
+        .collect()
+}
+
+/// A cell as a flat shaded triangle list, with the 6 vertices of face `i` (indexed like
+/// [`crate::NEIGHBOR_OFFSETS`]) at `6 * i..6 * i + 6`.
 pub fn rhombic_dodecahedron() -> Mesh {
-    let mut mesh = Mesh::new(bevy::render::render_resource::PrimitiveTopology::TriangleList);
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
     let mut vertices = vec![];
-    for rotation in [
-        Quat::from_rotation_y(0.0f32.to_radians()),
-        Quat::from_rotation_y(90.0f32.to_radians()),
-        Quat::from_rotation_y(-90.0f32.to_radians()),
-        Quat::from_rotation_y(-180.0f32.to_radians()),
-        Quat::from_rotation_x(90.0f32.to_radians()),
-        Quat::from_rotation_x(-90.0f32.to_radians()),
-    ] {
-        vertices.extend(
-            [
-                // Top
-                [0.5, 0.5, 0.5],
-                [-0.5, 0.5, 0.5],
-                [0.0, 0.0, 1.0],
-                // Bottom
-                [0.5, -0.5, 0.5],
-                [0.0, 0.0, 1.0],
-                [-0.5, -0.5, 0.5],
-                // Left
-                [-0.5, 0.5, 0.5],
-                [-0.5, -0.5, 0.5],
-                [0.0, 0.0, 1.0],
-                // Right
-                [0.5, 0.5, 0.5],
-                [0.0, 0.0, 1.0],
-                [0.5, -0.5, 0.5],
-            ]
-            .into_iter()
-            .map(|[x, y, z]| Transform::from_rotation(rotation) * Vec3 { x, y, z }),
-        );
+    let mut faces = vec![];
+    for face in 0..FACE_COUNT {
+        let [a, b, c, d] = rhombic_face_vertices(face);
+        // Split along the short diagonal, between the 3-valent corners
+        vertices.extend([a, b, d, b, c, d]);
+        faces.extend([1u32 << face; 6]);
     }
     mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
+    mesh.insert_attribute(ATTRIBUTE_CELL_FACES, faces);
     mesh.compute_flat_normals();
     mesh
 }
+
+/// The corners of face `face` (indexed like [`crate::NEIGHBOR_OFFSETS`]) of a cell centered at the
+/// origin, counter-clockwise seen from outside. Corners 0 and 2 are the 4-valent vertices and
+/// corners 1 and 3 the 3-valent ones.
+pub fn rhombic_face_vertices(face: usize) -> [Vec3; 4] {
+    FACE_VERTICES[face].map(|vertex| CELL_VERTICES[vertex])
+}
+
+/// Builds an indexed rhombic dodecahedron mesh with UVs, tangents and [`ATTRIBUTE_CELL_FACES`]. By
+/// default it has the same shape as [`rhombic_dodecahedron`].
+#[derive(Clone, Copy, Debug)]
+pub struct RhombicDodecahedronBuilder {
+    shared_vertices: bool,
+    atlas_tile: UVec2,
+    atlas_size: UVec2,
+    face_tiles: Option<[UVec2; FACE_COUNT]>,
+    face_colors: Option<[Color; FACE_COUNT]>,
+    size: f32,
+    bevel: f32,
+    inset: f32,
+}
+
+impl Default for RhombicDodecahedronBuilder {
+    fn default() -> Self {
+        Self {
+            shared_vertices: false,
+            atlas_tile: UVec2::ZERO,
+            atlas_size: UVec2::ONE,
+            face_tiles: None,
+            face_colors: None,
+            size: 1.0,
+            bevel: 0.0,
+            inset: 0.0,
+        }
+    }
+}
+
+/// The distance from the center of a unit cell to each of its faces.
+const INRADIUS: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// The distance from the center of a face to each of its edges, `1 / sqrt(6)`.
+const FACE_INRADIUS: f32 = 0.408_248_3;
+
+struct Polygon {
+    corners: Vec<Vec3>,
+    uvs: Vec<Vec2>,
+    normal: Vec3,
+    /// The faces of the cell this polygon is part of or replaces a corner or edge of, as in
+    /// [`ATTRIBUTE_CELL_FACES`].
+    faces: u32,
+}
+
+impl RhombicDodecahedronBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// With shared vertices the mesh has one vertex per corner with a smoothed normal, and UVs
+    /// come from a spherical projection since a shared vertex can't have a UV for each face.
+    /// Otherwise every face has its own vertices, and each rhombus is mapped onto the whole
+    /// atlas tile.
+    pub fn shared_vertices(mut self, shared_vertices: bool) -> Self {
+        self.shared_vertices = shared_vertices;
+        self
+    }
+
+    /// Maps UVs into tile `tile` of a texture atlas split into `atlas_size` tiles, counting from
+    /// the top left.
+    ///
+    /// Panics if the atlas has no tiles or `tile` is outside it.
+    pub fn atlas_tile(mut self, tile: UVec2, atlas_size: UVec2) -> Self {
+        assert!(
+            atlas_size.cmpgt(UVec2::ZERO).all(),
+            "atlas size must be positive, not {atlas_size}"
+        );
+        assert!(
+            tile.cmplt(atlas_size).all(),
+            "atlas tile {tile} is outside the {atlas_size} atlas"
+        );
+        self.atlas_tile = tile;
+        self.atlas_size = atlas_size;
+        self
+    }
+
+    /// Maps each face into its own tile of the atlas instead of `atlas_tile`, e.g. for blocks with
+    /// different top and sides. Beveled edges and corners use the tile of the lowest numbered
+    /// face they touch. Ignored with shared vertices.
+    ///
+    /// [`RhombicDodecahedronBuilder::build`] panics if a tile is outside the atlas size given to
+    /// [`RhombicDodecahedronBuilder::atlas_tile`].
+    pub fn face_atlas_tiles(mut self, tiles: [UVec2; FACE_COUNT]) -> Self {
+        self.face_tiles = Some(tiles);
+        self
+    }
+
+    /// Gives each face a vertex color, which [`StandardMaterial`] multiplies with its base color.
+    /// Vertices shared between faces, and beveled edges and corners, mix the colors of the faces
+    /// they touch.
+    pub fn face_colors(mut self, colors: [Color; FACE_COUNT]) -> Self {
+        self.face_colors = Some(colors);
+        self
+    }
+
+    /// Scales the cell, so that cells of this size tile a lattice scaled by `size`.
+    ///
+    /// Panics if `size` isn't positive, since there is no cell to inset or bevel.
+    pub fn size(mut self, size: f32) -> Self {
+        assert!(size > 0.0, "cell size must be positive, not {size}");
+        self.size = size;
+        self
+    }
+
+    /// Chamfers every edge, moving it this far in towards the middle of both faces it is on.
+    /// Clamped to just under the point where the faces would vanish.
+    pub fn bevel(mut self, bevel: f32) -> Self {
+        self.bevel = bevel;
+        self
+    }
+
+    /// Moves every face this far in towards the center, leaving a gap of twice this between
+    /// neighboring cells. Clamped to just under the point where the cell would vanish.
+    pub fn inset(mut self, inset: f32) -> Self {
+        self.inset = inset;
+        self
+    }
+
+    pub fn build(&self) -> Mesh {
+        for &tile in self.face_tiles.iter().flatten() {
+            assert!(
+                tile.cmplt(self.atlas_size).all(),
+                "atlas tile {tile} is outside the {} atlas",
+                self.atlas_size
+            );
+        }
+
+        let mut positions: Vec<[f32; 3]> = vec![];
+        let mut normals: Vec<[f32; 3]> = vec![];
+        let mut uvs: Vec<[f32; 2]> = vec![];
+        let mut faces: Vec<u32> = vec![];
+        let mut indices: Vec<u32> = vec![];
+
+        // Every face is tangent to the same sphere, so moving them all inwards is a scale
+        let inset = self.inset.clamp(0.0, INRADIUS * self.size * 0.999);
+        let scale = self.size - inset / INRADIUS;
+        let bevel = (self.bevel / scale).clamp(0.0, FACE_INRADIUS * 0.999);
+
+        for polygon in chamfered_polygons(bevel) {
+            let polygon_indices = polygon
+                .corners
+                .iter()
+                .zip(&polygon.uvs)
+                .map(|(&corner, &uv)| {
+                    let position = (corner * scale).to_array();
+                    let existing = self
+                        .shared_vertices
+                        .then(|| positions.iter().position(|&other| other == position))
+                        .flatten();
+                    if let Some(existing) = existing {
+                        faces[existing] |= polygon.faces;
+                        return existing as u32;
+                    }
+                    positions.push(position);
+                    faces.push(polygon.faces);
+                    if self.shared_vertices {
+                        normals.push(corner.normalize().to_array());
+                        uvs.push(
+                            self.atlas_uv(self.atlas_tile, spherical_uv(corner))
+                                .to_array(),
+                        );
+                    } else {
+                        let tile = self.face_tiles.map_or(self.atlas_tile, |tiles| {
+                            tiles[polygon.faces.trailing_zeros() as usize]
+                        });
+                        normals.push(polygon.normal.to_array());
+                        uvs.push(self.atlas_uv(tile, uv).to_array());
+                    }
+                    (positions.len() - 1) as u32
+                })
+                .collect::<Vec<_>>();
+            for i in 1..polygon_indices.len() - 1 {
+                indices.extend([
+                    polygon_indices[0],
+                    polygon_indices[i],
+                    polygon_indices[i + 1],
+                ]);
+            }
+        }
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+        if let Some(colors) = &self.face_colors {
+            mesh.insert_attribute(
+                Mesh::ATTRIBUTE_COLOR,
+                cell_face_vertex_colors(&faces, colors),
+            );
+        }
+        mesh.insert_attribute(ATTRIBUTE_CELL_FACES, faces);
+        mesh.set_indices(Some(Indices::U32(indices)));
+        mesh.generate_tangents()
+            .expect("rhombic dodecahedron mesh has every attribute tangents need");
+        mesh
+    }
+
+    fn atlas_uv(&self, tile: UVec2, uv: Vec2) -> Vec2 {
+        (tile.as_vec2() + uv) / self.atlas_size.as_vec2()
+    }
+}
+
+/// The faces of a unit cell with every edge chamfered by `bevel`, followed by the rectangles
+/// replacing the edges and the polygons replacing the vertices if `bevel` isn't zero.
+fn chamfered_polygons(bevel: f32) -> Vec<Polygon> {
+    // Shrink every face within its plane, keeping it a rhombus
+    let faces: [[Vec3; 4]; 12] = std::array::from_fn(|face| {
+        let corners = rhombic_face_vertices(face);
+        std::array::from_fn(|i| {
+            let to_previous = (corners[(i + 3) % 4] - corners[i]).normalize();
+            let to_next = (corners[(i + 1) % 4] - corners[i]).normalize();
+            let sin = to_previous.cross(to_next).length();
+            corners[i] + (to_previous + to_next) * (bevel / sin)
+        })
+    });
+
+    let mut polygons = faces
+        .iter()
+        .enumerate()
+        .map(|(face, corners)| Polygon {
+            // Starting at a 3-valent corner so that fans split along the short diagonal
+            corners: (1..5).map(|i| corners[i % 4]).collect(),
+            uvs: (1..5).map(|i| RHOMBUS_UVS[i % 4]).collect(),
+            normal: FACE_NORMALS[face],
+            faces: 1 << face,
+        })
+        .collect::<Vec<_>>();
+    if bevel <= 0.0 {
+        return polygons;
+    }
+
+    // The shrunk corner of `face` that came from `vertex`
+    let shrunk_corner = |face: usize, vertex: usize| {
+        let corner = FACE_VERTICES[face].iter().position(|&v| v == vertex);
+        faces[face][corner.unwrap()]
+    };
+
+    for (edge, &[a, b]) in EDGE_FACES.iter().enumerate() {
+        let [start, end] = EDGE_VERTICES[edge];
+        polygons.push(planar_polygon(
+            vec![
+                shrunk_corner(a, start),
+                shrunk_corner(a, end),
+                shrunk_corner(b, end),
+                shrunk_corner(b, start),
+            ],
+            1 << a | 1 << b,
+        ));
+    }
+
+    for (vertex, vertex_faces) in VERTEX_FACES.iter().enumerate() {
+        let axis = CELL_VERTICES[vertex].normalize();
+        let mut corners = vertex_faces
+            .iter()
+            .map(|&face| shrunk_corner(face, vertex))
+            .collect::<Vec<_>>();
+        let reference = (corners[0] - axis * corners[0].dot(axis)).normalize();
+        corners.sort_by(|a, b| {
+            let angle = |v: &Vec3| {
+                let v = *v - axis * v.dot(axis);
+                axis.dot(reference.cross(v)).atan2(reference.dot(v))
+            };
+            angle(a).total_cmp(&angle(b))
+        });
+        let faces = vertex_faces.iter().fold(0, |faces, face| faces | 1 << face);
+        polygons.push(planar_polygon(corners, faces));
+    }
+    polygons
+}
+
+/// Orients a flat convex polygon of the cell to face outwards and maps it onto the unit square.
+fn planar_polygon(mut corners: Vec<Vec3>, faces: u32) -> Polygon {
+    let centroid = corners.iter().sum::<Vec3>() / corners.len() as f32;
+    let mut normal = (corners[1] - corners[0])
+        .cross(corners[2] - corners[0])
+        .normalize();
+    if normal.dot(centroid) < 0.0 {
+        corners.reverse();
+        normal = -normal;
+    }
+
+    let tangent = (corners[1] - corners[0]).normalize();
+    let bitangent = normal.cross(tangent);
+    let projected = corners
+        .iter()
+        .map(|&corner| Vec2::new(corner.dot(tangent), -corner.dot(bitangent)))
+        .collect::<Vec<_>>();
+    let min = projected.iter().copied().reduce(Vec2::min).unwrap();
+    let max = projected.iter().copied().reduce(Vec2::max).unwrap();
+    Polygon {
+        uvs: projected
+            .into_iter()
+            .map(|uv| (uv - min) / (max - min))
+            .collect(),
+        corners,
+        normal,
+        faces,
+    }
+}
+
+/// The UVs of the corners returned by [`rhombic_face_vertices`], stretching the rhombus over the
+/// unit square.
+const RHOMBUS_UVS: [Vec2; 4] = [
+    Vec2::new(0.0, 1.0),
+    Vec2::new(1.0, 1.0),
+    Vec2::new(1.0, 0.0),
+    Vec2::new(0.0, 0.0),
+];
+
+fn spherical_uv(position: Vec3) -> Vec2 {
+    let direction = position.normalize();
+    Vec2::new(
+        0.5 + direction.z.atan2(direction.x) / std::f32::consts::TAU,
+        0.5 - direction.y.asin() / std::f32::consts::PI,
+    )
+}
+
+/// A built in mesh with the parameters it was generated from.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MeshPrimitive {
+    Plane {
+        size: f32,
+    },
+    Cube {
+        size: f32,
+    },
+    Sphere {
+        radius: f32,
+        sectors: usize,
+        stacks: usize,
+    },
+    RhombicDodecahedron {
+        size: f32,
+        bevel: f32,
+        inset: f32,
+    },
+}
+
+impl MeshPrimitive {
+    pub const ALL: [Self; 4] = [
+        Self::Plane { size: 1.0 },
+        Self::Cube { size: 1.0 },
+        Self::Sphere {
+            radius: 0.5,
+            sectors: 36,
+            stacks: 18,
+        },
+        Self::RhombicDodecahedron {
+            size: 1.0,
+            bevel: 0.0,
+            inset: 0.0,
+        },
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Plane { .. } => "Plane",
+            Self::Cube { .. } => "Cube",
+            Self::Sphere { .. } => "Sphere",
+            Self::RhombicDodecahedron { .. } => "Rhombic Dodecahedron",
+        }
+    }
+
+    pub fn mesh(&self) -> Mesh {
+        match *self {
+            Self::Plane { size } => shape::Plane::from_size(size).into(),
+            Self::Cube { size } => shape::Cube::new(size).into(),
+            Self::Sphere {
+                radius,
+                sectors,
+                stacks,
+            } => shape::UVSphere {
+                radius,
+                sectors,
+                stacks,
+            }
+            .into(),
+            Self::RhombicDodecahedron { size, bevel, inset } => RhombicDodecahedronBuilder::new()
+                .size(size)
+                .bevel(bevel)
+                .inset(inset)
+                .build(),
+        }
+    }
+}
+
+/// The [`MeshPrimitive`] each mesh asset was generated from, so it can be edited and generated
+/// again. Changing the parameters makes a new asset rather than changing this one, so other
+/// entities using the mesh keep it, and putting the old handle back brings back its parameters.
+#[derive(Resource, Default)]
+pub struct MeshPrimitives {
+    primitives: HashMap<HandleId, MeshPrimitive>,
+}
+
+impl MeshPrimitives {
+    pub fn get(&self, mesh: &Handle<Mesh>) -> Option<MeshPrimitive> {
+        self.primitives.get(&mesh.id()).copied()
+    }
+
+    pub fn insert(&mut self, mesh: &Handle<Mesh>, primitive: MeshPrimitive) {
+        self.primitives.insert(mesh.id(), primitive);
+    }
+}
+
+/// Adds the mesh of `primitive` as a new asset, remembering its parameters in [`MeshPrimitives`].
+pub fn add_primitive_mesh(world: &mut World, primitive: MeshPrimitive) -> Handle<Mesh> {
+    let mesh = world.resource_mut::<Assets<Mesh>>().add(primitive.mesh());
+    world
+        .resource_mut::<MeshPrimitives>()
+        .insert(&mesh, primitive);
+    mesh
+}
+
+pub(crate) fn forget_removed_mesh_primitives(
+    mut events: EventReader<AssetEvent<Mesh>>,
+    mut primitives: ResMut<MeshPrimitives>,
+) {
+    for event in events.iter() {
+        if let AssetEvent::Removed { handle } = event {
+            primitives.primitives.remove(&handle.id());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::VertexAttributeValues;
+
+    use super::*;
+
+    fn positions(mesh: &Mesh) -> &[[f32; 3]] {
+        match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+            Some(VertexAttributeValues::Float32x3(positions)) => positions,
+            _ => panic!("mesh has no positions"),
+        }
+    }
+
+    #[test]
+    fn builder_stays_finite_for_any_positive_size() {
+        for size in [f32::EPSILON, 1e-3, 0.5, 1.0, 100.0] {
+            for (bevel, inset) in [(0.0, 0.0), (0.1, 0.0), (0.0, 0.1), (10.0, 10.0)] {
+                let mesh = RhombicDodecahedronBuilder::new()
+                    .size(size)
+                    .bevel(bevel)
+                    .inset(inset)
+                    .build();
+                for &position in positions(&mesh) {
+                    let position = Vec3::from(position);
+                    assert!(position.is_finite(), "size {size} made {position}");
+                    assert!(position.length() <= size * 1.0001);
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "cell size must be positive")]
+    fn builder_rejects_zero_size() {
+        RhombicDodecahedronBuilder::new().size(0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "cell size must be positive")]
+    fn builder_rejects_negative_size() {
+        RhombicDodecahedronBuilder::new().size(-1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "cell size must be positive")]
+    fn builder_rejects_nan_size() {
+        RhombicDodecahedronBuilder::new().size(f32::NAN);
+    }
+
+    #[test]
+    #[should_panic(expected = "atlas size must be positive")]
+    fn builder_rejects_empty_atlases() {
+        RhombicDodecahedronBuilder::new().atlas_tile(UVec2::ZERO, UVec2::new(4, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "is outside the")]
+    fn builder_rejects_tiles_outside_the_atlas() {
+        RhombicDodecahedronBuilder::new().atlas_tile(UVec2::new(1, 4), UVec2::new(4, 4));
+    }
+
+    #[test]
+    #[should_panic(expected = "is outside the")]
+    fn builder_rejects_face_tiles_outside_the_atlas() {
+        let mut tiles = [UVec2::ZERO; FACE_COUNT];
+        tiles[5] = UVec2::new(2, 0);
+        RhombicDodecahedronBuilder::new()
+            .face_atlas_tiles(tiles)
+            .atlas_tile(UVec2::ZERO, UVec2::new(2, 2))
+            .build();
+    }
+}
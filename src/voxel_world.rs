@@ -0,0 +1,251 @@
+use std::io;
+
+use bevy::{
+    app::AppExit,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    chunk_of, chunk_origin, mesh_chunk_merged, MainCamera, RegionStore, RhombicCoord, RhombicGrid,
+};
+
+/// The voxels around the [`MainCamera`], streamed in and out of a [`RegionStore`] as it moves.
+#[derive(Resource)]
+pub struct VoxelWorld {
+    grid: RhombicGrid<u16>,
+    store: RegionStore,
+    /// How many chunks around the camera's chunk are kept loaded along each axis.
+    pub load_radius: i32,
+    loaded_chunks: HashSet<IVec3>,
+    unsaved_chunks: HashSet<IVec3>,
+    camera_chunk: Option<IVec3>,
+    chunk_entities: HashMap<IVec3, (Entity, Handle<Mesh>)>,
+    material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for VoxelWorld {
+    fn from_world(world: &mut World) -> Self {
+        let material = world
+            .resource_mut::<Assets<StandardMaterial>>()
+            .add(Color::rgb(0.7, 0.7, 0.7).into());
+        Self::new(RegionStore::new("world"), material)
+    }
+}
+
+impl VoxelWorld {
+    pub fn new(store: RegionStore, material: Handle<StandardMaterial>) -> Self {
+        Self {
+            grid: RhombicGrid::new(),
+            store,
+            load_radius: 2,
+            loaded_chunks: HashSet::default(),
+            unsaved_chunks: HashSet::default(),
+            camera_chunk: None,
+            chunk_entities: HashMap::default(),
+            material,
+        }
+    }
+
+    /// The currently loaded cells.
+    pub fn grid(&self) -> &RhombicGrid<u16> {
+        &self.grid
+    }
+
+    pub fn is_chunk_loaded(&self, chunk: IVec3) -> bool {
+        self.loaded_chunks.contains(&chunk)
+    }
+
+    /// Loads the cell's chunk first if it isn't loaded.
+    pub fn get(&mut self, cell: RhombicCoord) -> io::Result<Option<u16>> {
+        self.load_chunk(chunk_of(cell))?;
+        Ok(self.grid.get(cell).copied())
+    }
+
+    /// Loads the cell's chunk first if it isn't loaded.
+    pub fn set(&mut self, cell: RhombicCoord, value: u16) -> io::Result<Option<u16>> {
+        self.load_chunk(chunk_of(cell))?;
+        self.unsaved_chunks.insert(chunk_of(cell));
+        Ok(self.grid.set(cell, value))
+    }
+
+    /// Loads the cell's chunk first if it isn't loaded.
+    pub fn remove(&mut self, cell: RhombicCoord) -> io::Result<Option<u16>> {
+        self.load_chunk(chunk_of(cell))?;
+        self.unsaved_chunks.insert(chunk_of(cell));
+        Ok(self.grid.remove(cell))
+    }
+
+    /// Writes every loaded chunk that changed since it was loaded or last saved.
+    pub fn save(&mut self) -> io::Result<()> {
+        self.store
+            .save_chunks(&self.grid, self.unsaved_chunks.iter().copied())?;
+        self.unsaved_chunks.clear();
+        Ok(())
+    }
+
+    fn load_chunk(&mut self, chunk: IVec3) -> io::Result<()> {
+        if self.loaded_chunks.contains(&chunk) {
+            return Ok(());
+        }
+        for (cell, value) in self.store.load_chunk(chunk)?.unwrap_or_default() {
+            self.grid.set(cell, value);
+        }
+        self.loaded_chunks.insert(chunk);
+        Ok(())
+    }
+
+    /// Saves the chunks that changed all at once, so each region file is only rewritten once.
+    fn unload_chunks(&mut self, chunks: &[IVec3]) -> io::Result<()> {
+        let unsaved = chunks
+            .iter()
+            .copied()
+            .filter(|chunk| self.unsaved_chunks.contains(chunk))
+            .collect::<Vec<_>>();
+        self.store.save_chunks(&self.grid, unsaved)?;
+        for chunk in chunks {
+            self.unsaved_chunks.remove(chunk);
+            self.grid.remove_chunk(*chunk);
+            self.loaded_chunks.remove(chunk);
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn stream_voxel_chunks(
+    mut voxel_world: ResMut<VoxelWorld>,
+    camera: Query<&Transform, With<MainCamera>>,
+) {
+    let Ok(camera) = camera.get_single() else {
+        return;
+    };
+    let camera_chunk = chunk_of(RhombicCoord::from_world(camera.translation));
+    if voxel_world.camera_chunk == Some(camera_chunk) {
+        return;
+    }
+    voxel_world.camera_chunk = Some(camera_chunk);
+
+    let radius = voxel_world.load_radius;
+    let far_chunks = voxel_world
+        .loaded_chunks
+        .iter()
+        .copied()
+        .filter(|chunk| (*chunk - camera_chunk).abs().max_element() > radius)
+        .collect::<Vec<_>>();
+    if let Err(error) = voxel_world.unload_chunks(&far_chunks) {
+        error!("Failed to save chunks: {error}");
+    }
+
+    for x in -radius..=radius {
+        for y in -radius..=radius {
+            for z in -radius..=radius {
+                let chunk = camera_chunk + IVec3::new(x, y, z);
+                if let Err(error) = voxel_world.load_chunk(chunk) {
+                    error!("Failed to load chunk {chunk}: {error}");
+                }
+            }
+        }
+    }
+}
+
+/// Remeshes every loaded chunk whose cells changed, and despawns the meshes of unloaded chunks.
+pub(crate) fn update_voxel_chunk_meshes(
+    mut commands: Commands,
+    mut voxel_world: ResMut<VoxelWorld>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let voxel_world = voxel_world.as_mut();
+    for chunk in voxel_world.grid.take_dirty_chunks() {
+        let chunk_mesh = voxel_world
+            .is_chunk_loaded(chunk)
+            .then(|| mesh_chunk_merged(&voxel_world.grid, chunk))
+            .filter(|chunk_mesh| !chunk_mesh.is_empty());
+        match (chunk_mesh, voxel_world.chunk_entities.get(&chunk)) {
+            (Some(chunk_mesh), Some((_, mesh))) => {
+                *meshes.get_mut(mesh).unwrap() = chunk_mesh.into();
+            }
+            (Some(chunk_mesh), None) => {
+                let mesh = meshes.add(chunk_mesh.into());
+                let entity = commands
+                    .spawn(PbrBundle {
+                        mesh: mesh.clone(),
+                        material: voxel_world.material.clone(),
+                        transform: Transform::from_translation(chunk_origin(chunk).as_vec3()),
+                        ..default()
+                    })
+                    .id();
+                voxel_world.chunk_entities.insert(chunk, (entity, mesh));
+            }
+            (None, Some(&(entity, _))) => {
+                commands.entity(entity).despawn();
+                voxel_world.chunk_entities.remove(&chunk);
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+/// Saves the chunks that changed when the app is closing, since they'd otherwise only be saved
+/// when streamed out or with "Save World".
+pub(crate) fn save_voxel_world_on_exit(
+    mut exits: EventReader<AppExit>,
+    mut voxel_world: ResMut<VoxelWorld>,
+) {
+    if exits.iter().last().is_none() {
+        return;
+    }
+    match voxel_world.save() {
+        Ok(()) => info!("Saved voxel world"),
+        Err(error) => error!("Failed to save voxel world: {error}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temporary_store(name: &str) -> RegionStore {
+        RegionStore::new(
+            std::env::temp_dir().join(format!("grid-{name}-test-{}", std::process::id())),
+        )
+    }
+
+    #[test]
+    fn unloaded_chunks_are_saved() {
+        let store = temporary_store("unload");
+        let mut voxel_world = VoxelWorld::new(store.clone(), Handle::default());
+        let cells = [RhombicCoord::new(1, 1, 0), RhombicCoord::new(-40, 2, 0)];
+        for cell in cells {
+            voxel_world.set(cell, 7).unwrap();
+        }
+        let chunks = cells.map(chunk_of);
+        voxel_world.unload_chunks(&chunks).unwrap();
+        for (cell, chunk) in cells.into_iter().zip(chunks) {
+            assert!(!voxel_world.is_chunk_loaded(chunk));
+            assert_eq!(store.load_chunk(chunk).unwrap(), Some(vec![(cell, 7)]));
+            assert_eq!(voxel_world.get(cell).unwrap(), Some(7));
+        }
+        std::fs::remove_dir_all(store.directory()).unwrap();
+    }
+
+    #[test]
+    fn changes_are_saved_on_exit() {
+        let store = temporary_store("exit");
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(VoxelWorld::new(store.clone(), Handle::default()))
+            .add_system(save_voxel_world_on_exit);
+        let cell = RhombicCoord::new(3, 0, 1);
+        app.world.resource_mut::<VoxelWorld>().set(cell, 2).unwrap();
+        app.update();
+        assert_eq!(store.load_chunk(chunk_of(cell)).unwrap(), None);
+
+        app.world.send_event(AppExit);
+        app.update();
+        assert_eq!(
+            store.load_chunk(chunk_of(cell)).unwrap(),
+            Some(vec![(cell, 2)])
+        );
+        std::fs::remove_dir_all(store.directory()).unwrap();
+    }
+}